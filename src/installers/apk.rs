@@ -1,35 +1,314 @@
+use crate::installers::package_manager::{
+    self, InstalledPackage, PackageInstall, PackageManager, PackageManagerError, PlannedPackage,
+};
 use crate::utils;
+use crate::utils::command;
+use crate::utils::snapshot::parse_size;
 use anyhow::{Context, Result};
-use log::debug;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const APK_REPOSITORIES: &str = "/etc/apk/repositories";
+const APK_KEYS_DIR: &str = "/etc/apk/keys";
 
 /// Install packages using apk
-pub fn install(packages: &[String]) -> Result<()> {
+pub fn install(packages: &[String]) -> Result<PackageInstall> {
+    package_manager::install(&Apk::default(), packages)
+}
+
+/// apk backend for Alpine Linux with optional extra repositories and signing keys
+#[derive(Debug, Default)]
+pub struct Apk {
+    /// Repository URLs, optionally tagged for pinning (e.g. `@testing https://...`)
+    pub repositories: Vec<String>,
+    /// Signing keys as URLs or file paths
+    pub keys: Vec<String>,
+}
+
+impl PackageManager for Apk {
+    fn name(&self) -> &'static str {
+        "apk"
+    }
+
+    fn detect(&self) -> bool {
+        utils::os_detect::is_alpine()
+    }
+
+    fn unsupported_reason(&self) -> String {
+        "apk should be used on Alpine Linux distribution".to_string()
+    }
+
+    fn update(&self) -> Result<()> {
+        command::run(apk_update()).map_err(|e| PackageManagerError::Update("apk", e))?;
+        Ok(())
+    }
+
+    fn install(&self, packages: &[String]) -> Result<()> {
+        let added_keys = add_keys(&self.keys)?;
+        let original_repositories = match add_repositories(&self.repositories) {
+            Ok(original) => original,
+            Err(e) => {
+                remove_keys(&added_keys)?;
+                return Err(e);
+            }
+        };
+
+        debug!("Installing apk packages: {:?}", packages);
+        let installed = command::run(apk_add_pkgs(packages))
+            .map_err(|e| PackageManagerError::Install("apk", e));
+
+        if let Some(original) = original_repositories {
+            debug!("Restoring {}", APK_REPOSITORIES);
+            package_manager::install_file(original.as_bytes(), Path::new(APK_REPOSITORIES))
+                .with_context(|| format!("Failed to restore {}", APK_REPOSITORIES))?;
+        }
+        remove_keys(&added_keys)?;
+        installed?;
+        Ok(())
+    }
+
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        command::run(apk_del_pkgs(packages)).map_err(|e| PackageManagerError::Purge("apk", e))?;
+        Ok(())
+    }
+
+    fn clean(&self) -> Result<()> {
+        command::run(apk_clean()).map_err(|e| PackageManagerError::Clean("apk", e))?;
+        Ok(())
+    }
+
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
+        let mut cmd = std::process::Command::new("apk");
+        cmd.arg("info").arg("-v");
+        let output = command::run(cmd).map_err(|e| PackageManagerError::List("apk", e))?;
+
+        Ok(parse_apk_info(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn package_files(&self, packages: &[String]) -> Result<Vec<PathBuf>> {
+        if packages.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut cmd = std::process::Command::new("apk");
+        cmd.arg("info").arg("-L").args(packages);
+        let output = command::run(cmd).map_err(|e| PackageManagerError::Files("apk", e))?;
+
+        Ok(parse_apk_files(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn plan(&self, packages: &[String]) -> Result<Vec<PlannedPackage>> {
+        if !self.repositories.is_empty() {
+            warn!("Packages from repositories that are not configured yet are not simulated");
+        }
+
+        let mut cmd = std::process::Command::new("apk");
+        cmd.arg("add")
+            .arg("--simulate")
+            .arg("--no-cache")
+            .args(packages);
+        let output = command::run(cmd).map_err(|e| PackageManagerError::Plan("apk", e))?;
+        let mut planned = parse_apk_simulation(&String::from_utf8_lossy(&output.stdout));
+        if planned.is_empty() {
+            return Ok(planned);
+        }
+
+        let mut cmd = std::process::Command::new("apk");
+        cmd.arg("info")
+            .arg("-s")
+            .args(planned.iter().map(|p| &p.name));
+        match command::run(cmd) {
+            Ok(output) => {
+                let sizes = parse_apk_sizes(&String::from_utf8_lossy(&output.stdout));
+                for package in &mut planned {
+                    if let Some(version) = &package.version {
+                        package.size = sizes.get(&format!("{}-{}", package.name, version)).copied();
+                    }
+                }
+            }
+            Err(e) => warn!("Could not look up package sizes: {}", e),
+        }
+
+        Ok(planned)
+    }
+}
+
+/// Parse `apk add --simulate` lines of the form `(1/4) Installing name (version)`
+fn parse_apk_simulation(output: &str) -> Vec<PlannedPackage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.trim().split_once(") ")?;
+            let rest = rest
+                .strip_prefix("Installing ")
+                .or_else(|| rest.strip_prefix("Upgrading "))?;
+            let (name, version) = rest.split_once(" (")?;
+            // Upgrades report `old -> new`
+            let version = version
+                .split(')')
+                .next()
+                .and_then(|v| v.rsplit(' ').next())
+                .unwrap_or_default();
+            Some(PlannedPackage {
+                name: name.to_string(),
+                version: Some(version.to_string()),
+                size: None,
+            })
+        })
+        .collect()
+}
+
+/// Parse `apk info -s` output into sizes keyed by `name-version`
+fn parse_apk_sizes(output: &str) -> HashMap<String, u64> {
+    let mut sizes = HashMap::new();
+    let mut lines = output.lines();
+    while let Some(line) = lines.next() {
+        if let Some(package) = line.trim().strip_suffix(" installed size:")
+            && let Some(Ok(size)) = lines.next().map(parse_size)
+        {
+            sizes.insert(package.to_string(), size);
+        }
+    }
+    sizes
+}
+
+/// Append repositories to the repositories file, returning its original contents
+fn add_repositories(repositories: &[String]) -> Result<Option<String>> {
+    if repositories.is_empty() {
+        return Ok(None);
+    }
+
+    let lines = repositories
+        .iter()
+        .map(|r| repository_line(r))
+        .collect::<Result<Vec<_>>>()?;
+
+    let original = fs::read_to_string(APK_REPOSITORIES).unwrap_or_default();
+    let mut contents = original.clone();
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    for line in lines {
+        debug!("Adding apk repository: {}", line);
+        contents.push_str(&line);
+        contents.push('\n');
+    }
+
+    package_manager::install_file(contents.as_bytes(), Path::new(APK_REPOSITORIES))
+        .with_context(|| format!("Failed to write {}", APK_REPOSITORIES))?;
+    Ok(Some(original))
+}
+
+/// Normalize a repository to a repositories file line, accepting `@tag URL` or `@tag=URL`
+fn repository_line(repository: &str) -> Result<String> {
+    let repository = repository.trim();
+    let (tag, url) = match repository.strip_prefix('@') {
+        Some(tagged) => {
+            let (tag, url) = tagged
+                .split_once(['=', ' '])
+                .with_context(|| format!("Invalid tagged apk repository: {}", repository))?;
+            (Some(tag.trim()), url.trim())
+        }
+        None => (None, repository),
+    };
+
     anyhow::ensure!(
-        utils::os_detect::is_alpine(),
-        "apk should be used on Alpine Linux distribution"
+        url.starts_with("http://") || url.starts_with("https://") || url.starts_with('/'),
+        "Invalid apk repository URL: {}",
+        url
     );
 
-    debug!("Updating apk repositories");
-    apk_update()
-        .output()
-        .map(|o| debug!("Apk update output: {:?}", o))
-        .context("Failed to update apk repositories")?;
+    Ok(match tag {
+        Some(tag) => format!("@{} {}", tag, url),
+        None => url.to_string(),
+    })
+}
+
+/// Install signing keys that are not already trusted, returning the files written
+fn add_keys(keys: &[String]) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for key in keys {
+        if let Err(e) = add_key(key, &mut written) {
+            remove_keys(&written)?;
+            return Err(e);
+        }
+    }
+    Ok(written)
+}
 
-    debug!("Installing apk packages: {:?}", packages);
-    apk_add_pkgs(packages)
-        .output()
-        .map(|o| debug!("Apk add output: {:?}", o))
-        .context("Failed to install apk packages")?;
+fn add_key(key: &str, written: &mut Vec<PathBuf>) -> Result<()> {
+    let file_name = key
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .with_context(|| format!("Invalid apk key: {}", key))?;
+    let path = Path::new(APK_KEYS_DIR).join(file_name);
+    if path.exists() {
+        debug!("apk key already trusted: {}", path.display());
+        return Ok(());
+    }
 
-    debug!("Cleaning up apk cache");
-    apk_clean()
-        .output()
-        .map(|o| debug!("Apk clean output: {:?}", o))
-        .context("Failed to clean apk cache")?;
+    let data = if key.starts_with("http://") || key.starts_with("https://") {
+        let response = reqwest::blocking::Client::new()
+            .get(key)
+            .header("User-Agent", "picolayer")
+            .send()
+            .with_context(|| format!("Failed to download apk key: {}", key))?;
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to download apk key: {}", response.status());
+        }
+        response.bytes()?.to_vec()
+    } else {
+        fs::read(key).with_context(|| format!("Failed to read apk key: {}", key))?
+    };
+
+    debug!("Adding apk key: {}", path.display());
+    package_manager::install_file(&data, &path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    written.push(path);
+    Ok(())
+}
 
+fn remove_keys(keys: &[PathBuf]) -> Result<()> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+    debug!("Removing apk keys: {:?}", keys);
+    let mut cmd = std::process::Command::new("sudo");
+    cmd.arg("rm").arg("-f").arg("--").args(keys);
+    command::run(cmd).context("Failed to remove apk keys")?;
     Ok(())
 }
 
+/// Parse `apk info -v` lines of the form `name-version-rN`
+fn parse_apk_info(output: &str) -> Vec<InstalledPackage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().rsplitn(3, '-');
+            let release = parts.next()?;
+            let version = parts.next()?;
+            let name = parts.next()?;
+            Some(InstalledPackage {
+                name: name.to_string(),
+                version: format!("{}-{}", version, release),
+            })
+        })
+        .collect()
+}
+
+/// Parse `apk info -L` output, which lists paths relative to the root under a
+/// `name-version contains:` header per package
+fn parse_apk_files(output: &str) -> Vec<PathBuf> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.ends_with(" contains:"))
+        .map(|line| Path::new("/").join(line))
+        .collect()
+}
+
 fn apk() -> std::process::Command {
     let mut cmd = std::process::Command::new("sudo");
     cmd.arg("apk");
@@ -54,6 +333,12 @@ fn apk_add_pkgs(packages: &[String]) -> std::process::Command {
     cmd
 }
 
+fn apk_del_pkgs(packages: &[String]) -> std::process::Command {
+    let mut cmd = apk();
+    cmd.arg("del").args(packages);
+    cmd
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -66,4 +351,49 @@ mod tests {
         let result = install(&packages);
         let _ = result;
     }
+
+    #[test]
+    #[serial]
+    fn test_repository_line() {
+        let edge = "https://dl-cdn.alpinelinux.org/alpine/edge/testing";
+        assert_eq!(repository_line(edge).unwrap(), edge);
+        assert_eq!(
+            repository_line(&format!("@testing {}", edge)).unwrap(),
+            format!("@testing {}", edge)
+        );
+        assert_eq!(
+            repository_line(&format!("@testing={}", edge)).unwrap(),
+            format!("@testing {}", edge)
+        );
+        assert!(repository_line("@testing").is_err());
+        assert!(repository_line("edge/testing").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_apk_simulation() {
+        let output = "(1/2) Installing ca-certificates (20240705-r0)\n\
+                      (2/2) Upgrading curl (8.9.0-r0 -> 8.9.1-r0)\n\
+                      OK: 10 MiB in 16 packages\n";
+        let planned = parse_apk_simulation(output);
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].name, "ca-certificates");
+        assert_eq!(planned[0].version.as_deref(), Some("20240705-r0"));
+        assert_eq!(planned[1].name, "curl");
+        assert_eq!(planned[1].version.as_deref(), Some("8.9.1-r0"));
+
+        let sizes = parse_apk_sizes("curl-8.9.1-r0 installed size:\n292 KiB\n\n");
+        assert_eq!(sizes["curl-8.9.1-r0"], 292 * 1024);
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_apk_info() {
+        let packages = parse_apk_info("musl-1.2.5-r0\nca-certificates-bundle-20240705-r0\n");
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "musl");
+        assert_eq!(packages[0].version, "1.2.5-r0");
+        assert_eq!(packages[1].name, "ca-certificates-bundle");
+        assert_eq!(packages[1].version, "20240705-r0");
+    }
 }
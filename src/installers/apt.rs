@@ -1,66 +1,130 @@
 use crate::installers::apt_get;
+use crate::installers::apt_repository::{self, AptRepository};
+use crate::installers::package_manager::{
+    self, InstalledPackage, PackageInstall, PackageManager, PackageManagerError, PlannedPackage,
+};
 use crate::utils;
-use anyhow::{Context, Result};
-use log::{debug, info, warn};
+use crate::utils::command;
+use crate::utils::leftovers::Leftover;
+use anyhow::Result;
+use log::{info, warn};
+use std::path::PathBuf;
 
 /// Install packages using apt
 pub fn install(
     packages: &[String],
     ppas: Option<&[String]>,
     force_ppas_on_non_ubuntu: bool,
-) -> Result<()> {
-    anyhow::ensure!(
-        utils::os_detect::is_debian_like() && which::which("apt").is_ok(),
-        "apt should be used on Debian-like distributions (Debian, Ubuntu, etc.)"
-    );
-
-    let mut ppas = ppas.map(|p| p.to_vec()).unwrap_or_default();
-    if !ppas.is_empty() && !utils::os_detect::is_ubuntu() && !force_ppas_on_non_ubuntu {
-        warn!("PPAs are ignored on non-Ubuntu distros!");
-        info!("Use --force-ppas-on-non-ubuntu to include them anyway.");
-        ppas.clear();
+) -> Result<PackageInstall> {
+    let apt = Apt {
+        ppas: ppas.map(|p| p.to_vec()).unwrap_or_default(),
+        force_ppas_on_non_ubuntu,
+        ..Default::default()
+    };
+    package_manager::install(&apt, packages)
+}
+
+/// apt backend with optional PPAs and signed repositories
+#[derive(Debug, Default)]
+pub struct Apt {
+    pub ppas: Vec<String>,
+    pub force_ppas_on_non_ubuntu: bool,
+    pub repositories: Vec<AptRepository>,
+}
+
+impl PackageManager for Apt {
+    fn name(&self) -> &'static str {
+        "apt"
+    }
+
+    fn detect(&self) -> bool {
+        utils::os_detect::is_debian_like() && which::which("apt").is_ok()
     }
 
-    debug!("Updating apt repositories");
-    apt_update()
-        .output()
-        .map(|o| debug!("Apt update output: {:?}", o))
-        .context("Failed to update repositories")?;
+    fn unsupported_reason(&self) -> String {
+        "apt should be used on Debian-like distributions (Debian, Ubuntu, etc.)".to_string()
+    }
 
-    let mut installed_ppas = Vec::new();
-    let mut installed_ppa_packages = Vec::new();
+    fn update(&self) -> Result<()> {
+        command::run(apt_update()).map_err(|e| PackageManagerError::Update("apt", e))?;
+        Ok(())
+    }
+
+    fn install(&self, packages: &[String]) -> Result<()> {
+        let mut ppas = self.ppas.clone();
+        if !ppas.is_empty() && !utils::os_detect::is_ubuntu() && !self.force_ppas_on_non_ubuntu {
+            warn!("PPAs are ignored on non-Ubuntu distros!");
+            info!("Use --force-ppas-on-non-ubuntu to include them anyway.");
+            ppas.clear();
+        }
+
+        let mut installed_ppas = Vec::new();
+        let mut installed_ppa_packages = Vec::new();
+
+        if !ppas.is_empty() {
+            let (ppas_added, ppa_pkgs) = apt_get::add_ppas(&ppas)?;
+            installed_ppas.extend(ppas_added);
+            installed_ppa_packages.extend(ppa_pkgs);
+        }
+
+        let added_repositories = apt_repository::add_repositories(&self.repositories)?;
+        // The added repositories are removed whether the update or the install failed
+        let installed = (|| {
+            if !added_repositories.is_empty() {
+                command::run(apt_update()).map_err(|e| PackageManagerError::Update("apt", e))?;
+            }
+
+            info!("Installing apt packages: {:?}", packages);
+            command::run(apt_install(packages)).map_err(|e| PackageManagerError::Install("apt", e))
+        })();
+
+        apt_repository::remove_repositories(&added_repositories)?;
+        installed?;
+
+        if !installed_ppas.is_empty() {
+            info!("Removing added PPAs");
+            command::run(apt_remove_ppas(&installed_ppas))
+                .map_err(|e| PackageManagerError::RemoveRepository("apt", e))?;
+        }
+
+        if !installed_ppa_packages.is_empty() {
+            info!("Purging packages installed for PPA support");
+            self.remove(&installed_ppa_packages)?;
+        }
+
+        Ok(())
+    }
 
-    if !ppas.is_empty() {
-        let (ppas_added, ppa_pkgs) = apt_get::add_ppas(&ppas)?;
-        installed_ppas.extend(ppas_added);
-        installed_ppa_packages.extend(ppa_pkgs);
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        command::run(apt_purge(packages)).map_err(|e| PackageManagerError::Purge("apt", e))?;
+        Ok(())
     }
 
-    info!("Installing apt packages: {:?}", packages);
-    apt_install(packages)
-        .output()
-        .map(|o| debug!("Apt install output: {:?}", o))
-        .context("Failed to install apt packages")?;
-
-    info!("Removing added PPAs and packages installed for PPA support");
-    apt_remove_ppas(&installed_ppas)
-        .output()
-        .map(|o| debug!("Apt remove PPAs output: {:?}", o))
-        .context("Failed to remove PPAs")?;
-
-    info!("Purging packages installed for PPA support");
-    apt_purge(installed_ppa_packages.as_slice())
-        .output()
-        .map(|o| debug!("Apt purge output: {:?}", o))
-        .context("Failed to purge package installed for PPA support")?;
-
-    info!("Cleaning up apt cache");
-    apt_clean()
-        .output()
-        .map(|o| debug!("Apt clean output: {:?}", o))
-        .context("Failed to clean apt cache")?;
-
-    Ok(())
+    fn clean(&self) -> Result<()> {
+        command::run(apt_clean()).map_err(|e| PackageManagerError::Clean("apt", e))?;
+        Ok(())
+    }
+
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
+        apt_get::list_dpkg_packages("apt")
+    }
+
+    fn package_files(&self, packages: &[String]) -> Result<Vec<PathBuf>> {
+        apt_get::list_dpkg_files("apt", packages)
+    }
+
+    fn leftovers(&self) -> &'static [Leftover] {
+        apt_get::APT_LEFTOVERS
+    }
+
+    fn plan(&self, packages: &[String]) -> Result<Vec<PlannedPackage>> {
+        if !self.ppas.is_empty() || !self.repositories.is_empty() {
+            warn!(
+                "Packages from PPAs and repositories that are not configured yet are not simulated"
+            );
+        }
+        apt_get::plan_apt_packages("apt", packages)
+    }
 }
 
 fn apt() -> std::process::Command {
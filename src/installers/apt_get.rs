@@ -1,69 +1,151 @@
+use crate::installers::apt_repository::{self, AptRepository};
+use crate::installers::package_manager::{
+    self, InstalledPackage, PackageInstall, PackageManager, PackageManagerError, PlannedPackage,
+};
 use crate::utils;
-use anyhow::{Context, Result};
+use crate::utils::command;
+use crate::utils::leftovers::Leftover;
+use anyhow::Result;
 use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Package lists fetched by `apt-get update`, which simulations resolve against
+const APT_LISTS_DIR: &str = "/var/lib/apt/lists";
 
 const PPA_SUPPORT_PACKAGES: &[&str] = &["software-properties-common"];
 const PPA_SUPPORT_PACKAGES_DEBIAN: &[&str] = &["python3-launchpadlib"];
 
+/// Package lists, debconf backups and logs that apt leaves behind, shared by the Debian-family backends
+pub(crate) const APT_LEFTOVERS: &[Leftover] = &[
+    Leftover::Created("/var/lib/apt/lists/*"),
+    Leftover::Created("/var/cache/debconf/*-old"),
+    Leftover::Created("/var/log/apt/eipp.log.xz"),
+    Leftover::Appended("/var/log/apt/history.log"),
+    Leftover::Appended("/var/log/apt/term.log"),
+    Leftover::Appended("/var/log/dpkg.log"),
+    Leftover::Appended("/var/log/alternatives.log"),
+];
+
 /// Install packages using apt-get with optional PPAs
 pub fn install(
     packages: &[String],
     ppas: Option<&[String]>,
     force_ppas_on_non_ubuntu: bool,
-) -> Result<()> {
-    anyhow::ensure!(
-        utils::os_detect::is_debian_like() && which::which("apt-get").is_ok(),
-        "apt-get should be used on Debian-like distributions (Debian, Ubuntu, etc.)"
-    );
+) -> Result<PackageInstall> {
+    let apt_get = AptGet {
+        ppas: ppas.map(|p| p.to_vec()).unwrap_or_default(),
+        force_ppas_on_non_ubuntu,
+        ..Default::default()
+    };
+    package_manager::install(&apt_get, packages)
+}
+
+/// apt-get backend with optional PPAs and signed repositories
+#[derive(Debug, Default)]
+pub struct AptGet {
+    pub ppas: Vec<String>,
+    pub force_ppas_on_non_ubuntu: bool,
+    pub repositories: Vec<AptRepository>,
+}
 
-    let mut ppas = ppas.map(|p| p.to_vec()).unwrap_or_default();
-    if !ppas.is_empty() && !utils::os_detect::is_ubuntu() && !force_ppas_on_non_ubuntu {
-        warn!("PPAs are ignored on non-Ubuntu distros!");
-        info!("Use --force-ppas-on-non-ubuntu to include them anyway.");
-        ppas.clear();
+impl PackageManager for AptGet {
+    fn name(&self) -> &'static str {
+        "apt-get"
     }
 
-    debug!("Updating apt repositories");
-    apt_update()
-        .output()
-        .map(|o| debug!("Apt update output: {:?}", o))
-        .context("Failed to update repositories")?;
+    fn detect(&self) -> bool {
+        utils::os_detect::is_debian_like() && which::which("apt-get").is_ok()
+    }
 
-    let mut installed_ppas = Vec::new();
-    let mut installed_ppa_packages = Vec::new();
+    fn unsupported_reason(&self) -> String {
+        "apt-get should be used on Debian-like distributions (Debian, Ubuntu, etc.)".to_string()
+    }
+
+    fn update(&self) -> Result<()> {
+        command::run(apt_update()).map_err(|e| PackageManagerError::Update("apt-get", e))?;
+        Ok(())
+    }
+
+    fn install(&self, packages: &[String]) -> Result<()> {
+        let mut ppas = self.ppas.clone();
+        if !ppas.is_empty() && !utils::os_detect::is_ubuntu() && !self.force_ppas_on_non_ubuntu {
+            warn!("PPAs are ignored on non-Ubuntu distros!");
+            info!("Use --force-ppas-on-non-ubuntu to include them anyway.");
+            ppas.clear();
+        }
+
+        let mut installed_ppas = Vec::new();
+        let mut installed_ppa_packages = Vec::new();
+
+        if !ppas.is_empty() {
+            let (ppas_added, ppa_pkgs) = add_ppas(&ppas)?;
+            installed_ppas = ppas_added;
+            installed_ppa_packages = ppa_pkgs;
+        }
+
+        let added_repositories = apt_repository::add_repositories(&self.repositories)?;
+        // The added repositories are removed whether the update or the install failed
+        let installed = (|| {
+            if !added_repositories.is_empty() {
+                command::run(apt_update())
+                    .map_err(|e| PackageManagerError::Update("apt-get", e))?;
+            }
+
+            debug!("Installing apt packages: {:?}", packages);
+            command::run(apt_install(packages))
+                .map_err(|e| PackageManagerError::Install("apt-get", e))
+        })();
+
+        apt_repository::remove_repositories(&added_repositories)?;
+        installed?;
+
+        if !installed_ppas.is_empty() {
+            debug!("Removing added PPAs");
+            command::run(apt_remove_ppas(&installed_ppas))
+                .map_err(|e| PackageManagerError::RemoveRepository("apt-get", e))?;
+        }
 
-    if !ppas.is_empty() {
-        let (ppas_added, ppa_pkgs) = add_ppas(&ppas)?;
-        installed_ppas = ppas_added;
-        installed_ppa_packages = ppa_pkgs;
+        if !installed_ppa_packages.is_empty() {
+            debug!("Purging packages installed for PPA support");
+            self.remove(&installed_ppa_packages)?;
+        }
+
+        Ok(())
     }
 
-    debug!("Installing apt packages: {:?}", packages);
-    apt_install(packages)
-        .output()
-        .map(|o| debug!("Apt install output: {:?}", o))
-        .context("Failed to install apt packages")?;
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        command::run(apt_purge(packages)).map_err(|e| PackageManagerError::Purge("apt-get", e))?;
+        Ok(())
+    }
 
-    if !ppas.is_empty() {
-        debug!("Removing added PPAs and packages installed for PPA support");
-        apt_remove_ppas(&installed_ppas)
-            .output()
-            .map(|o| debug!("Apt remove PPAs output: {:?}", o))
-            .context("Failed to remove PPAs")?;
-        apt_purge(installed_ppa_packages.as_slice())
-            .output()
-            .map(|o| debug!("Apt purge output: {:?}", o))
-            .context("Failed to purge packages installed for PPA support")?;
+    fn clean(&self) -> Result<()> {
+        let mut clean = apt_get();
+        clean.arg("clean");
+        command::run(clean).map_err(|e| PackageManagerError::Clean("apt-get", e))?;
+        Ok(())
     }
 
-    debug!("Cleaning up apt cache");
-    apt_get()
-        .arg("clean")
-        .output()
-        .map(|o| debug!("Apt clean output: {:?}", o))
-        .context("Failed to clean apt cache")?;
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
+        list_dpkg_packages("apt-get")
+    }
 
-    Ok(())
+    fn package_files(&self, packages: &[String]) -> Result<Vec<PathBuf>> {
+        list_dpkg_files("apt-get", packages)
+    }
+
+    fn leftovers(&self) -> &'static [Leftover] {
+        APT_LEFTOVERS
+    }
+
+    fn plan(&self, packages: &[String]) -> Result<Vec<PlannedPackage>> {
+        if !self.ppas.is_empty() || !self.repositories.is_empty() {
+            warn!(
+                "Packages from PPAs and repositories that are not configured yet are not simulated"
+            );
+        }
+        plan_apt_packages("apt-get", packages)
+    }
 }
 
 pub fn add_ppas(ppas: &[String]) -> Result<(Vec<String>, Vec<String>)> {
@@ -95,31 +177,24 @@ pub fn add_ppas(ppas: &[String]) -> Result<(Vec<String>, Vec<String>)> {
         let status = dpkg().arg("-s").arg(pkg).status().map(|o| o.success())?;
 
         if !status && which::which(pkg).is_err() {
-            apt_install(&[pkg.to_string()])
-                .status()
-                .context("Failed to install packages required for PPA support")?;
+            command::run(apt_install(&[pkg.to_string()]))
+                .map_err(|e| PackageManagerError::Install("apt-get", e))?;
             installed_packages.push(pkg.to_string());
         }
     }
 
     if which::which("add-apt-repository").is_ok() {
         for ppa in &normalized_ppas {
-            apt_add_repository()
-                .arg("-y")
-                .arg(ppa)
-                .output()
-                .map(|o| debug!("Apt add PPA output: {:?}", o))
-                .context("Failed to add PPA")?;
+            let mut cmd = apt_add_repository();
+            cmd.arg("-y").arg(ppa);
+            command::run(cmd).map_err(|e| PackageManagerError::AddRepository("apt-get", e))?;
             installed_ppas.push(ppa.clone());
         }
     } else {
         warn!("add-apt-repository command not found");
     }
 
-    apt_update()
-        .output()
-        .map(|o| debug!("Apt update output: {:?}", o))
-        .context("Failed to update repositories")?;
+    command::run(apt_update()).map_err(|e| PackageManagerError::Update("apt-get", e))?;
 
     Ok((installed_ppas, installed_packages))
 }
@@ -166,6 +241,122 @@ fn apt_purge(packages: &[String]) -> std::process::Command {
     cmd
 }
 
+/// List packages dpkg reports as installed, shared by the Debian-family backends
+pub(crate) fn list_dpkg_packages(manager: &'static str) -> Result<Vec<InstalledPackage>> {
+    let mut cmd = std::process::Command::new("dpkg-query");
+    cmd.arg("-W")
+        .arg("-f=${db:Status-Abbrev}\t${Package}\t${Version}\n");
+    let output = command::run(cmd).map_err(|e| PackageManagerError::List(manager, e))?;
+
+    let installed = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.strip_prefix("ii"))
+        .map(|line| line.trim_start().to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(package_manager::parse_installed(&installed, '\t'))
+}
+
+/// List files dpkg installed for packages, shared by the Debian-family backends
+pub(crate) fn list_dpkg_files(manager: &'static str, packages: &[String]) -> Result<Vec<PathBuf>> {
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut cmd = std::process::Command::new("dpkg-query");
+    cmd.arg("-L").args(packages);
+    let output = command::run(cmd).map_err(|e| PackageManagerError::Files(manager, e))?;
+
+    Ok(package_manager::parse_file_list(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Simulate an install with `apt-get -s` and look up installed sizes with `apt-cache`,
+/// shared by the Debian-family backends
+pub(crate) fn plan_apt_packages(
+    manager: &'static str,
+    packages: &[String],
+) -> Result<Vec<PlannedPackage>> {
+    anyhow::ensure!(
+        has_package_lists(Path::new(APT_LISTS_DIR)),
+        "No apt package lists in {}, so nothing can be simulated; run `apt-get update` first",
+        APT_LISTS_DIR
+    );
+
+    let mut cmd = std::process::Command::new("apt-get");
+    cmd.arg("install")
+        .arg("-s")
+        .arg("--no-install-recommends")
+        .args(packages);
+    let output = command::run(cmd).map_err(|e| PackageManagerError::Plan(manager, e))?;
+    let mut planned = parse_apt_simulation(&String::from_utf8_lossy(&output.stdout));
+    if planned.is_empty() {
+        return Ok(planned);
+    }
+
+    let mut cmd = std::process::Command::new("apt-cache");
+    cmd.arg("show")
+        .arg("--no-all-versions")
+        .args(planned.iter().map(|p| &p.name));
+    match command::run(cmd) {
+        Ok(output) => {
+            let sizes = parse_installed_sizes(&String::from_utf8_lossy(&output.stdout));
+            for package in &mut planned {
+                package.size = sizes.get(&package.name).copied();
+            }
+        }
+        Err(e) => warn!("Could not look up package sizes: {}", e),
+    }
+
+    Ok(planned)
+}
+
+/// Whether `apt-get update` has fetched any package index into the lists directory
+fn has_package_lists(dir: &Path) -> bool {
+    std::fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().contains("_Packages"))
+    })
+}
+
+/// Parse the `Inst` lines of `apt-get -s install`, e.g.
+/// `Inst libcurl4 [7.88.1-9] (7.88.1-10 Debian:12.5/stable [amd64])`
+fn parse_apt_simulation(output: &str) -> Vec<PlannedPackage> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("Inst "))
+        .filter_map(|line| {
+            let name = line.split_whitespace().next()?;
+            let version = line
+                .split_once('(')
+                .and_then(|(_, rest)| rest.split_whitespace().next());
+            Some(PlannedPackage {
+                name: name.split(':').next().unwrap_or(name).to_string(),
+                version: version.map(|v| v.trim_end_matches(')').to_string()),
+                size: None,
+            })
+        })
+        .collect()
+}
+
+/// Parse `Installed-Size` (in KiB) per package from `apt-cache show`
+fn parse_installed_sizes(output: &str) -> HashMap<String, u64> {
+    let mut sizes = HashMap::new();
+    let mut package = None;
+    for line in output.lines() {
+        if let Some(name) = line.strip_prefix("Package:") {
+            package = Some(name.trim().to_string());
+        } else if let Some(size) = line.strip_prefix("Installed-Size:")
+            && let (Some(name), Ok(kib)) = (package.take(), size.trim().parse::<u64>())
+        {
+            sizes.insert(name, kib * 1024);
+        }
+    }
+    sizes
+}
+
 fn dpkg() -> std::process::Command {
     let mut cmd = std::process::Command::new("sudo");
     cmd.arg("dpkg");
@@ -186,6 +377,44 @@ mod tests {
         let _ = result;
     }
 
+    #[test]
+    #[serial]
+    fn test_has_package_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("partial")).unwrap();
+        std::fs::write(dir.path().join("lock"), "").unwrap();
+        assert!(!has_package_lists(dir.path()));
+        assert!(!has_package_lists(&dir.path().join("missing")));
+
+        std::fs::write(
+            dir.path()
+                .join("deb.debian.org_debian_dists_bookworm_main_binary-amd64_Packages.lz4"),
+            "",
+        )
+        .unwrap();
+        assert!(has_package_lists(dir.path()));
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_apt_simulation() {
+        let output = "NOTE: This is only a simulation!\n\
+                      Inst libcurl4 [7.88.1-9] (7.88.1-10 Debian:12.5/stable [amd64])\n\
+                      Inst curl (7.88.1-10 Debian:12.5/stable [amd64])\n\
+                      Conf curl (7.88.1-10 Debian:12.5/stable [amd64])\n";
+        let planned = parse_apt_simulation(output);
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].name, "libcurl4");
+        assert_eq!(planned[0].version.as_deref(), Some("7.88.1-10"));
+        assert_eq!(planned[1].name, "curl");
+
+        let sizes = parse_installed_sizes(
+            "Package: curl\nVersion: 7.88.1-10\nInstalled-Size: 500\n\nPackage: libcurl4\nInstalled-Size: 1024\n",
+        );
+        assert_eq!(sizes["curl"], 500 * 1024);
+        assert_eq!(sizes["libcurl4"], 1024 * 1024);
+    }
+
     #[test]
     #[serial]
     fn test_apt_get_invalid_package() {
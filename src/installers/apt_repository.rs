@@ -0,0 +1,215 @@
+use crate::installers::gh_release::GpgVerifier;
+use crate::installers::package_manager::install_file;
+use crate::utils::command;
+use anyhow::{Context, Result};
+use log::{debug, info};
+use pgp::ser::Serialize;
+use reqwest::blocking::Client;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+const KEYRINGS_DIR: &str = "/etc/apt/keyrings";
+const SOURCES_DIR: &str = "/etc/apt/sources.list.d";
+
+/// An apt repository signed by a vendor keyring (e.g. Docker, HashiCorp, NodeSource)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AptRepository {
+    /// Keyring URL, file path or key content
+    pub keyring: String,
+    pub uri: String,
+    pub suite: String,
+    pub components: Vec<String>,
+}
+
+impl AptRepository {
+    /// File name stem derived from the repository URI
+    fn name(&self) -> String {
+        let location = self
+            .uri
+            .split_once("://")
+            .map_or(self.uri.as_str(), |(_, rest)| rest);
+        let name: String = location
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        format!("picolayer-{}", name.trim_matches('-'))
+    }
+
+    fn keyring_path(&self) -> PathBuf {
+        PathBuf::from(KEYRINGS_DIR).join(format!("{}.gpg", self.name()))
+    }
+
+    fn sources_path(&self) -> PathBuf {
+        PathBuf::from(SOURCES_DIR).join(format!("{}.sources", self.name()))
+    }
+
+    /// deb822 source entry pointing at the keyring
+    fn deb822(&self) -> String {
+        let mut sources = format!("Types: deb\nURIs: {}\nSuites: {}\n", self.uri, self.suite);
+        if !self.components.is_empty() {
+            sources.push_str(&format!("Components: {}\n", self.components.join(" ")));
+        }
+        sources.push_str(&format!("Signed-By: {}\n", self.keyring_path().display()));
+        sources
+    }
+}
+
+impl FromStr for AptRepository {
+    type Err = anyhow::Error;
+
+    /// Parse `KEYRING URI SUITE [COMPONENT...]`
+    fn from_str(s: &str) -> Result<Self> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [keyring, uri, suite, components @ ..] = fields.as_slice() else {
+            anyhow::bail!(
+                "Invalid apt repository: {} (expected KEYRING URI SUITE [COMPONENT...])",
+                s
+            );
+        };
+        anyhow::ensure!(
+            uri.starts_with("http://") || uri.starts_with("https://"),
+            "Invalid apt repository URI: {}",
+            uri
+        );
+
+        Ok(Self {
+            keyring: keyring.to_string(),
+            uri: uri.to_string(),
+            suite: suite.to_string(),
+            components: components.iter().map(|c| c.to_string()).collect(),
+        })
+    }
+}
+
+/// Write a keyring and deb822 source for each repository, returning the files written
+/// and the keyrings directory if it had to be created
+pub fn add_repositories(repositories: &[AptRepository]) -> Result<Vec<PathBuf>> {
+    let client = Client::new();
+    let verifier = GpgVerifier::new(&client);
+    let mut written = Vec::new();
+
+    for repository in repositories {
+        if let Err(e) = add_repository(&verifier, repository, &mut written) {
+            remove_repositories(&written)?;
+            return Err(e);
+        }
+    }
+
+    Ok(written)
+}
+
+fn add_repository(
+    verifier: &GpgVerifier,
+    repository: &AptRepository,
+    written: &mut Vec<PathBuf>,
+) -> Result<()> {
+    info!("Adding apt repository: {}", repository.uri);
+
+    let key = verifier
+        .load_public_key(&repository.keyring)
+        .with_context(|| format!("Failed to load keyring for {}", repository.uri))?;
+    let keyring = key.to_bytes().context("Failed to serialize keyring")?;
+
+    if !Path::new(KEYRINGS_DIR).exists() {
+        let mut cmd = Command::new("sudo");
+        cmd.arg("install")
+            .arg("-d")
+            .arg("-m")
+            .arg("0755")
+            .arg(KEYRINGS_DIR);
+        command::run(cmd)
+            .with_context(|| format!("Failed to create directory: {}", KEYRINGS_DIR))?;
+        written.push(PathBuf::from(KEYRINGS_DIR));
+    }
+
+    let keyring_path = repository.keyring_path();
+    install_file(&keyring, &keyring_path)
+        .with_context(|| format!("Failed to write keyring: {}", keyring_path.display()))?;
+    written.push(keyring_path);
+
+    let sources_path = repository.sources_path();
+    install_file(repository.deb822().as_bytes(), &sources_path)
+        .with_context(|| format!("Failed to write source: {}", sources_path.display()))?;
+    written.push(sources_path);
+
+    Ok(())
+}
+
+/// Remove the keyrings, sources and directory written by `add_repositories`
+pub fn remove_repositories(paths: &[PathBuf]) -> Result<()> {
+    let (dirs, files): (Vec<&PathBuf>, Vec<&PathBuf>) =
+        paths.iter().partition(|path| path.is_dir());
+
+    if !files.is_empty() {
+        debug!("Removing {:?}", files);
+        let mut cmd = Command::new("sudo");
+        cmd.arg("rm").arg("-f").arg("--").args(&files);
+        command::run(cmd).context("Failed to remove apt repositories")?;
+    }
+
+    // Keep the directory if something else put a keyring there in the meantime
+    for dir in dirs {
+        debug!("Removing {}", dir.display());
+        let mut cmd = Command::new("sudo");
+        cmd.arg("rmdir")
+            .arg("--ignore-fail-on-non-empty")
+            .arg("--")
+            .arg(dir);
+        command::run(cmd).with_context(|| format!("Failed to remove {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_repository() {
+        let repository: AptRepository = "https://download.docker.com/linux/debian/gpg https://download.docker.com/linux/debian bookworm stable"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            repository.keyring,
+            "https://download.docker.com/linux/debian/gpg"
+        );
+        assert_eq!(repository.suite, "bookworm");
+        assert_eq!(repository.components, vec!["stable".to_string()]);
+        assert_eq!(
+            repository.sources_path(),
+            PathBuf::from(
+                "/etc/apt/sources.list.d/picolayer-download-docker-com-linux-debian.sources"
+            )
+        );
+        assert_eq!(
+            repository.deb822(),
+            "Types: deb\n\
+             URIs: https://download.docker.com/linux/debian\n\
+             Suites: bookworm\n\
+             Components: stable\n\
+             Signed-By: /etc/apt/keyrings/picolayer-download-docker-com-linux-debian.gpg\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_flat_repository() {
+        let repository: AptRepository = "/tmp/key.asc https://example.com/apt ./".parse().unwrap();
+        assert!(repository.components.is_empty());
+        assert!(!repository.deb822().contains("Components"));
+    }
+
+    #[test]
+    fn test_parse_invalid_repository() {
+        assert!(
+            "https://example.com/key.gpg"
+                .parse::<AptRepository>()
+                .is_err()
+        );
+        assert!(
+            "key.gpg example.com bookworm main"
+                .parse::<AptRepository>()
+                .is_err()
+        );
+    }
+}
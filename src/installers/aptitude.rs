@@ -1,36 +1,77 @@
 use crate::installers::apt_get;
+use crate::installers::package_manager::{
+    self, InstalledPackage, PackageInstall, PackageManager, PackageManagerError, PlannedPackage,
+};
 use crate::utils;
+use crate::utils::command;
+use crate::utils::leftovers::Leftover;
 use anyhow::{Context, Result};
 use log::debug;
+use std::path::PathBuf;
 
 /// Install packages using aptitude
-pub fn install(packages: &[String]) -> Result<()> {
-    anyhow::ensure!(
-        utils::os_detect::is_debian_like(),
-        "aptitude should be used on Debian-like distributions (Debian, Ubuntu, etc.)"
-    );
-
-    debug!("Updating aptitude repositories");
-    aptitude_update()
-        .output()
-        .map(|o| debug!("Aptitude update output: {:?}", o))
-        .context("Failed to update aptitude repositories")?;
-
-    debug!("Installing aptitude");
-    install_aptitude().context("Failed to install aptitude")?;
-
-    debug!("Installing aptitude packages: {:?}", packages);
-    aptitude_install(packages)
-        .output()
-        .map(|o| debug!("Aptitude install output: {:?}", o))
-        .context("Failed to install packages with aptitude")?;
-
-    debug!("Cleaning aptitude cache");
-    aptitude_clean()
-        .status()
-        .context("Failed to clean aptitude cache")?;
+pub fn install(packages: &[String]) -> Result<PackageInstall> {
+    package_manager::install(&Aptitude, packages)
+}
 
-    Ok(())
+/// aptitude backend, installing aptitude itself through apt-get when missing
+#[derive(Debug, Default)]
+pub struct Aptitude;
+
+impl PackageManager for Aptitude {
+    fn name(&self) -> &'static str {
+        "aptitude"
+    }
+
+    fn detect(&self) -> bool {
+        utils::os_detect::is_debian_like()
+    }
+
+    fn unsupported_reason(&self) -> String {
+        "aptitude should be used on Debian-like distributions (Debian, Ubuntu, etc.)".to_string()
+    }
+
+    fn update(&self) -> Result<()> {
+        debug!("Installing aptitude");
+        install_aptitude().context("Failed to install aptitude")?;
+
+        command::run(aptitude_update()).map_err(|e| PackageManagerError::Update("aptitude", e))?;
+        Ok(())
+    }
+
+    fn install(&self, packages: &[String]) -> Result<()> {
+        debug!("Installing aptitude packages: {:?}", packages);
+        command::run(aptitude_install(packages))
+            .map_err(|e| PackageManagerError::Install("aptitude", e))?;
+        Ok(())
+    }
+
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        command::run(aptitude_purge(packages))
+            .map_err(|e| PackageManagerError::Purge("aptitude", e))?;
+        Ok(())
+    }
+
+    fn clean(&self) -> Result<()> {
+        command::run(aptitude_clean()).map_err(|e| PackageManagerError::Clean("aptitude", e))?;
+        Ok(())
+    }
+
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
+        apt_get::list_dpkg_packages("aptitude")
+    }
+
+    fn package_files(&self, packages: &[String]) -> Result<Vec<PathBuf>> {
+        apt_get::list_dpkg_files("aptitude", packages)
+    }
+
+    fn leftovers(&self) -> &'static [Leftover] {
+        apt_get::APT_LEFTOVERS
+    }
+
+    fn plan(&self, packages: &[String]) -> Result<Vec<PlannedPackage>> {
+        apt_get::plan_apt_packages("aptitude", packages)
+    }
 }
 
 /// Installs the aptitude package using apt-get if not already installed
@@ -60,6 +101,12 @@ fn aptitude_clean() -> std::process::Command {
     cmd
 }
 
+fn aptitude_purge(packages: &[String]) -> std::process::Command {
+    let mut cmd = aptitude();
+    cmd.arg("purge").arg("-y").args(packages);
+    cmd
+}
+
 fn aptitude_install(packages: &[String]) -> std::process::Command {
     let mut cmd = aptitude();
     cmd.arg("install")
@@ -1,32 +1,65 @@
-use anyhow::{Context, Result};
+use crate::installers::package_manager::{
+    self, InstalledPackage, PackageInstall, PackageManager, PackageManagerError,
+};
+use crate::utils::command;
+use anyhow::Result;
 use log::debug;
 
 /// Install packages using Homebrew
-pub fn install(packages: &[String]) -> Result<()> {
-    anyhow::ensure!(
-        which::which("brew").is_ok(),
-        "Homebrew not installed or not in PATH"
-    );
-
-    debug!("Updating Homebrew");
-    brew_update()
-        .output()
-        .map(|o| debug!("Brew update output: {:?}", o))
-        .context("Failed to update Homebrew")?;
-
-    debug!("Installing Homebrew packages: {:?}", packages);
-    brew_install(packages)
-        .output()
-        .map(|o| debug!("Brew install output: {:?}", o))
-        .context("Failed to install Homebrew packages")?;
-
-    debug!("Cleaning up Homebrew cache");
-    brew_cleanup()
-        .output()
-        .map(|o| debug!("Brew cleanup output: {:?}", o))
-        .context("Failed to clean up Homebrew cache")?;
-
-    Ok(())
+pub fn install(packages: &[String]) -> Result<PackageInstall> {
+    package_manager::install(&Brew, packages)
+}
+
+/// Homebrew backend
+#[derive(Debug, Default)]
+pub struct Brew;
+
+impl PackageManager for Brew {
+    fn name(&self) -> &'static str {
+        "brew"
+    }
+
+    fn detect(&self) -> bool {
+        which::which("brew").is_ok()
+    }
+
+    fn unsupported_reason(&self) -> String {
+        "Homebrew not installed or not in PATH".to_string()
+    }
+
+    fn update(&self) -> Result<()> {
+        command::run(brew_update()).map_err(|e| PackageManagerError::Update("brew", e))?;
+        Ok(())
+    }
+
+    fn install(&self, packages: &[String]) -> Result<()> {
+        debug!("Installing Homebrew packages: {:?}", packages);
+        command::run(brew_install(packages))
+            .map_err(|e| PackageManagerError::Install("brew", e))?;
+        Ok(())
+    }
+
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        command::run(brew_uninstall(packages))
+            .map_err(|e| PackageManagerError::Purge("brew", e))?;
+        Ok(())
+    }
+
+    fn clean(&self) -> Result<()> {
+        command::run(brew_cleanup()).map_err(|e| PackageManagerError::Clean("brew", e))?;
+        Ok(())
+    }
+
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
+        let mut cmd = brew();
+        cmd.arg("list").arg("--versions");
+        let output = command::run(cmd).map_err(|e| PackageManagerError::List("brew", e))?;
+
+        Ok(package_manager::parse_installed(
+            &String::from_utf8_lossy(&output.stdout),
+            ' ',
+        ))
+    }
 }
 
 fn brew() -> std::process::Command {
@@ -39,6 +72,12 @@ fn brew_install(packages: &[String]) -> std::process::Command {
     cmd
 }
 
+fn brew_uninstall(packages: &[String]) -> std::process::Command {
+    let mut cmd = brew();
+    cmd.arg("uninstall").args(packages);
+    cmd
+}
+
 fn brew_update() -> std::process::Command {
     let mut cmd = brew();
     cmd.arg("update");
@@ -0,0 +1,169 @@
+use crate::installers::package_manager::{self, InstalledPackage, PackageManager, PackagePlan};
+use crate::installers::pkg::{self, PackageMapping};
+use crate::utils::leftovers::Leftovers;
+use crate::utils::os_detect;
+use crate::utils::output::OutputFormat;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// Configuration for running a command with temporary build dependencies
+pub struct BuildConfig<'a> {
+    pub build_deps: &'a [String],
+    pub mappings: &'a [PackageMapping],
+    pub command: &'a [String],
+    pub working_dir: &'a str,
+    /// Where the build command's stdout goes
+    pub output: OutputFormat,
+}
+
+/// Build dependencies that would be installed and the command run between install and purge
+#[derive(Debug, Serialize)]
+pub struct BuildPlan {
+    pub build_deps: PackagePlan,
+    pub command: Vec<String>,
+    pub working_dir: String,
+}
+
+impl fmt::Display for BuildPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.build_deps)?;
+        writeln!(
+            f,
+            "then run `{}` in {} and purge the packages it added",
+            self.command.join(" "),
+            self.working_dir
+        )
+    }
+}
+
+/// A build command that ran with temporary build dependencies
+#[derive(Debug, Serialize)]
+pub struct BuildResult {
+    pub manager: &'static str,
+    pub command: Vec<String>,
+    /// Packages the build dependencies added, purged after the build
+    pub purged: Vec<InstalledPackage>,
+}
+
+/// Report the build dependencies that would be installed without running the build
+pub fn plan(config: &BuildConfig) -> Result<BuildPlan> {
+    validate(config)?;
+    Ok(BuildPlan {
+        build_deps: pkg::plan(config.build_deps, config.mappings)?,
+        command: config.command.to_vec(),
+        working_dir: config.working_dir.to_string(),
+    })
+}
+
+/// Install build dependencies, run the command, then purge the packages the dependencies added
+pub fn run(config: &BuildConfig) -> Result<BuildResult> {
+    validate(config)?;
+
+    let distro = os_detect::detect_distro()?;
+    let manager = pkg::native_package_manager(&distro)?;
+    let build_deps = pkg::resolve_packages(config.build_deps, config.mappings, &distro);
+    anyhow::ensure!(manager.detect(), manager.unsupported_reason());
+
+    let leftovers = Leftovers::capture(manager.leftovers());
+    let before = manager.list_installed()?;
+
+    info!("Updating {} repositories", manager.name());
+    manager.update()?;
+
+    info!(
+        "Installing {} build dependencies: {:?}",
+        manager.name(),
+        build_deps
+    );
+    let installed = manager.install(&build_deps);
+    let after = manager.list_installed()?;
+    let added = package_manager::newly_installed(&before, &after);
+
+    let built = installed.and_then(|_| run_command(config));
+
+    purge(manager.as_ref(), &added)?;
+    info!("Cleaning up {} cache", manager.name());
+    manager.clean()?;
+    leftovers.remove()?;
+
+    built?;
+    Ok(BuildResult {
+        manager: manager.name(),
+        command: config.command.to_vec(),
+        purged: after
+            .into_iter()
+            .filter(|package| added.contains(&package.name))
+            .collect(),
+    })
+}
+
+fn validate(config: &BuildConfig) -> Result<()> {
+    anyhow::ensure!(!config.command.is_empty(), "No build command provided");
+    anyhow::ensure!(
+        Path::new(config.working_dir).is_dir(),
+        "Working directory does not exist: {}",
+        config.working_dir
+    );
+    Ok(())
+}
+
+fn run_command(config: &BuildConfig) -> Result<()> {
+    info!("Running build command: {:?}", config.command);
+    let status = std::process::Command::new(&config.command[0])
+        .args(&config.command[1..])
+        .current_dir(config.working_dir)
+        .stdout(config.output.child_stdout())
+        .stderr(std::process::Stdio::inherit())
+        .status()
+        .with_context(|| format!("Failed to execute build command: {}", config.command[0]))?;
+
+    if !status.success() {
+        anyhow::bail!("Build command failed with exit code: {:?}", status.code());
+    }
+    Ok(())
+}
+
+fn purge(manager: &dyn PackageManager, added: &[String]) -> Result<()> {
+    if added.is_empty() {
+        warn!("Build dependencies did not add any packages, nothing to purge");
+        return Ok(());
+    }
+
+    info!("Purging {} build dependencies: {:?}", manager.name(), added);
+    manager.remove(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_build_without_command() {
+        let result = run(&BuildConfig {
+            build_deps: &["make".to_string()],
+            mappings: &[],
+            command: &[],
+            working_dir: ".",
+            output: OutputFormat::Text,
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_build_missing_working_dir() {
+        let result = run(&BuildConfig {
+            build_deps: &["make".to_string()],
+            mappings: &[],
+            command: &["true".to_string()],
+            working_dir: "/nonexistent/build/dir",
+            output: OutputFormat::Text,
+        });
+        assert!(result.unwrap_err().to_string().contains("does not exist"));
+    }
+}
@@ -1,9 +1,17 @@
+use crate::installers::package_manager::{self, InstalledPackage, PackageManager};
+use crate::installers::pkg;
+use crate::installers::session::Session;
+use crate::utils::os_detect;
+use crate::utils::receipts::{self, Receipt, ReceiptFile, ReceiptPackage};
+use crate::utils::snapshot::{self, Snapshot};
 use anyhow::{Context, Result};
 use log::{debug, info, warn};
+use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 const ORDERED_BASE_USERS: &[&str] = &["vscode", "node", "codespace"];
@@ -95,7 +103,7 @@ pub fn parse_oci_ref(oci_input: &str) -> Result<ParsedOciRef> {
 }
 
 /// Get OCI manifest from registry
-fn get_manifest(parsed_ref: &ParsedOciRef) -> Result<OciManifest> {
+fn get_manifest(client: &Client, parsed_ref: &ParsedOciRef) -> Result<OciManifest> {
     let url = format!(
         "https://{}/v2/{}/manifests/{}",
         parsed_ref.registry, parsed_ref.path, parsed_ref.version
@@ -103,7 +111,6 @@ fn get_manifest(parsed_ref: &ParsedOciRef) -> Result<OciManifest> {
 
     debug!("Fetching manifest from: {}", url);
 
-    let client = reqwest::blocking::Client::new();
     let response = client
         .get(&url)
         .header("Accept", "application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.v2+json")
@@ -120,9 +127,9 @@ fn get_manifest(parsed_ref: &ParsedOciRef) -> Result<OciManifest> {
     Ok(manifest)
 }
 
-/// Download and extract OCI layer
-fn download_and_extract_layer(parsed_ref: &ParsedOciRef, output_dir: &Path) -> Result<()> {
-    let manifest = get_manifest(parsed_ref)?;
+/// Download the feature layer, a gzipped tarball
+fn download_layer(client: &Client, parsed_ref: &ParsedOciRef) -> Result<Vec<u8>> {
+    let manifest = get_manifest(client, parsed_ref)?;
 
     if manifest.layers.is_empty() {
         anyhow::bail!("Feature OCI manifest has no layers");
@@ -136,7 +143,6 @@ fn download_and_extract_layer(parsed_ref: &ParsedOciRef, output_dir: &Path) -> R
 
     debug!("Downloading layer from: {}", blob_url);
 
-    let client = reqwest::blocking::Client::new();
     let response = client
         .get(&blob_url)
         .header("User-Agent", "picolayer")
@@ -148,8 +154,12 @@ fn download_and_extract_layer(parsed_ref: &ParsedOciRef, output_dir: &Path) -> R
     }
 
     let blob_data = response.bytes().context("Failed to read blob data")?;
+    Ok(blob_data.to_vec())
+}
 
-    let decoder = flate2::read::GzDecoder::new(&blob_data[..]);
+/// Extract the feature layer into a directory
+fn unpack_layer(layer: &[u8], output_dir: &Path) -> Result<()> {
+    let decoder = flate2::read::GzDecoder::new(layer);
     let mut archive = tar::Archive::new(decoder);
     archive
         .unpack(output_dir)
@@ -212,21 +222,21 @@ fn resolve_remote_user(remote_user: Option<&str>) -> Result<(String, String)> {
     Ok(("root".to_string(), "/root".to_string()))
 }
 
-/// Load feature metadata from directory
-fn load_feature_metadata(feature_dir: &Path) -> Result<Feature> {
-    let metadata_path = feature_dir.join("devcontainer-feature.json");
+/// Read feature metadata from the layer without extracting it
+fn read_feature_metadata(layer: &[u8]) -> Result<Feature> {
+    let decoder = flate2::read::GzDecoder::new(layer);
+    let mut archive = tar::Archive::new(decoder);
 
-    if !metadata_path.exists() {
-        anyhow::bail!("Feature metadata file not found: devcontainer-feature.json");
+    for entry in archive.entries().context("Failed to read layer archive")? {
+        let entry = entry.context("Failed to read layer archive")?;
+        if entry.path()?.file_name() == Some("devcontainer-feature.json".as_ref()) {
+            let feature: Feature =
+                serde_json::from_reader(entry).context("Failed to parse feature metadata")?;
+            return Ok(feature);
+        }
     }
 
-    let metadata_content =
-        fs::read_to_string(&metadata_path).context("Failed to read feature metadata")?;
-
-    let feature: Feature =
-        serde_json::from_str(&metadata_content).context("Failed to parse feature metadata")?;
-
-    Ok(feature)
+    anyhow::bail!("Feature metadata file not found: devcontainer-feature.json")
 }
 
 /// Resolve feature options with defaults
@@ -287,52 +297,154 @@ fn set_container_env(feature: &Feature) -> Result<()> {
     Ok(())
 }
 
-/// Install a devcontainer feature from an OCI reference
-pub fn install(
+/// A resolved feature and the environment its install script would run with
+#[derive(Debug, Serialize)]
+pub struct FeaturePlan {
+    pub feature: Feature,
+    pub remote_user: String,
+    pub remote_user_home: String,
+    pub options: HashMap<String, String>,
+    pub env_vars: HashMap<String, String>,
+    #[serde(skip)]
+    layer: Vec<u8>,
+}
+
+/// A devcontainer feature whose install script completed
+#[derive(Debug, Serialize)]
+pub struct FeatureInstall {
+    pub reference: String,
+    pub id: String,
+    pub version: Option<String>,
+    pub remote_user: String,
+    /// Why the install script did not run, when the feature was already installed
+    pub skipped: Option<String>,
+}
+
+impl fmt::Display for FeaturePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "devcontainer-feature would install {} v{} for {} (home: {}):",
+            self.feature.id,
+            self.feature.version.as_deref().unwrap_or("unknown"),
+            self.remote_user,
+            self.remote_user_home
+        )?;
+        for (key, value) in self.options.iter().collect::<BTreeMap<_, _>>() {
+            writeln!(f, "  option: {}={}", key, value)?;
+        }
+        for (key, value) in self.env_vars.iter().collect::<BTreeMap<_, _>>() {
+            writeln!(f, "  env: {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolve a feature's options and install environment without running it
+pub fn plan(
     feature_ref: &str,
     options: Option<HashMap<String, String>>,
     remote_user: Option<&str>,
     envs: Option<HashMap<String, String>>,
-) -> Result<()> {
-    info!("Installing devcontainer feature: {}", feature_ref);
+) -> Result<FeaturePlan> {
+    plan_with(&Client::new(), feature_ref, options, remote_user, envs)
+}
 
+fn plan_with(
+    client: &Client,
+    feature_ref: &str,
+    options: Option<HashMap<String, String>>,
+    remote_user: Option<&str>,
+    envs: Option<HashMap<String, String>>,
+) -> Result<FeaturePlan> {
     let parsed_ref = parse_oci_ref(feature_ref)?;
     debug!("Parsed OCI ref: {:?}", parsed_ref);
 
-    let temp_dir = tempfile::tempdir().context("Failed to create temporary directory")?;
-
-    info!("Downloading and extracting feature...");
-    download_and_extract_layer(&parsed_ref, temp_dir.path())?;
+    info!("Downloading feature...");
+    let layer = download_layer(client, &parsed_ref)?;
 
-    let feature = load_feature_metadata(temp_dir.path())?;
+    let feature = read_feature_metadata(&layer)?;
     info!(
         "Feature: {} v{}",
         feature.id,
         feature.version.as_deref().unwrap_or("unknown")
     );
 
-    let (remote_user_name, remote_user_home) = resolve_remote_user(remote_user)?;
-    info!(
-        "Installing for user: {} (home: {})",
-        remote_user_name, remote_user_home
-    );
-
-    let resolved_options = resolve_options(&feature, options);
-    debug!("Resolved options: {:?}", resolved_options);
+    let (remote_user, remote_user_home) = resolve_remote_user(remote_user)?;
+    let options = resolve_options(&feature, options);
+    debug!("Resolved options: {:?}", options);
 
     let mut env_vars = envs.unwrap_or_default();
-    env_vars.insert("_REMOTE_USER".to_string(), remote_user_name.clone());
+    env_vars.insert("_REMOTE_USER".to_string(), remote_user.clone());
     env_vars.insert("_REMOTE_USER_HOME".to_string(), remote_user_home.clone());
 
-    for (key, value) in resolved_options {
-        env_vars.insert(key.to_uppercase(), value);
+    for (key, value) in &options {
+        env_vars.insert(key.to_uppercase(), value.clone());
     }
 
+    Ok(FeaturePlan {
+        feature,
+        remote_user,
+        remote_user_home,
+        options,
+        env_vars,
+        layer,
+    })
+}
+
+/// Install a devcontainer feature from an OCI reference
+pub fn install(
+    feature_ref: &str,
+    options: Option<HashMap<String, String>>,
+    remote_user: Option<&str>,
+    envs: Option<HashMap<String, String>>,
+    force: bool,
+) -> Result<FeatureInstall> {
+    install_in(
+        &Session::new()?,
+        feature_ref,
+        options,
+        remote_user,
+        envs,
+        force,
+    )
+}
+
+/// Install a devcontainer feature from an OCI reference using a shared session
+pub fn install_in(
+    session: &Session,
+    feature_ref: &str,
+    options: Option<HashMap<String, String>>,
+    remote_user: Option<&str>,
+    envs: Option<HashMap<String, String>>,
+    force: bool,
+) -> Result<FeatureInstall> {
+    info!("Installing devcontainer feature: {}", feature_ref);
+
+    let plan = plan_with(session.client(), feature_ref, options, remote_user, envs)?;
+    if !force && let Some(skipped) = already_installed(feature_ref, &plan) {
+        return Ok(skipped);
+    }
+    info!(
+        "Installing for user: {} (home: {})",
+        plan.remote_user, plan.remote_user_home
+    );
+
+    let temp_dir =
+        tempfile::tempdir_in(session.temp_dir()).context("Failed to create temporary directory")?;
+
+    info!("Extracting feature...");
+    unpack_layer(&plan.layer, temp_dir.path())?;
+
     let install_script = temp_dir.path().join("install.sh");
     if !install_script.exists() {
         anyhow::bail!("Feature install.sh script not found");
     }
 
+    let roots = receipt_roots(&plan.remote_user_home);
+    let before = Snapshot::capture(&roots);
+    let packages_before = PackageSnapshot::capture();
+
     info!("Executing feature installation script...");
 
     #[cfg(unix)]
@@ -343,7 +455,8 @@ pub fn install(
         fs::set_permissions(&install_script, perms)?;
     }
 
-    let env_string: Vec<String> = env_vars
+    let env_string: Vec<String> = plan
+        .env_vars
         .iter()
         .map(|(k, v)| format!("{}=\"{}\"", k, v.replace("\"", "\\\"")))
         .collect();
@@ -376,9 +489,9 @@ pub fn install(
 
     info!("Feature installation script completed successfully");
 
-    set_container_env(&feature)?;
+    set_container_env(&plan.feature)?;
 
-    if let Some(entrypoint) = &feature.entrypoint {
+    if let Some(entrypoint) = &plan.feature.entrypoint {
         info!("Executing feature entrypoint: {}", entrypoint);
         let output = Command::new("sh")
             .arg("-c")
@@ -395,7 +508,171 @@ pub fn install(
     }
 
     info!("Devcontainer feature installation completed successfully");
-    Ok(())
+
+    let files = before.new_files(&Snapshot::capture(&roots));
+    if let Err(e) = record_receipt(feature_ref, &plan, files, packages_before.as_ref()) {
+        warn!("Failed to record receipt for {}: {:#}", plan.feature.id, e);
+    }
+
+    Ok(FeatureInstall {
+        reference: feature_ref.to_string(),
+        id: plan.feature.id,
+        version: plan.feature.version,
+        remote_user: plan.remote_user,
+        skipped: None,
+    })
+}
+
+/// Report the install as skipped when a receipt records the same feature version and options
+fn already_installed(feature_ref: &str, plan: &FeaturePlan) -> Option<FeatureInstall> {
+    let receipt = receipts::get(&plan.feature.id).ok().flatten()?;
+    // Features also write caches and logs that later cleanups remove, so the recorded
+    // files are not checked
+    let satisfied = receipt.reference == feature_ref
+        && receipt.version == receipt_version(plan)
+        && receipt.options == receipt_options(plan);
+    if !satisfied {
+        return None;
+    }
+
+    let reason = format!(
+        "{} {} is already installed with the same options (use --force to reinstall)",
+        plan.feature.id, receipt.version
+    );
+    info!("{}", reason);
+    Some(FeatureInstall {
+        reference: feature_ref.to_string(),
+        id: plan.feature.id.clone(),
+        version: plan.feature.version.clone(),
+        remote_user: plan.remote_user.clone(),
+        skipped: Some(reason),
+    })
+}
+
+/// Directories searched for files written by a feature, including the remote user's home
+fn receipt_roots(remote_user_home: &str) -> Vec<PathBuf> {
+    let mut roots: Vec<String> = snapshot::DEFAULT_ROOTS
+        .iter()
+        .map(|root| root.to_string())
+        .collect();
+    roots.push(remote_user_home.to_string());
+    snapshot::watched_roots(&roots)
+}
+
+/// Packages installed before a feature script ran, to tell which ones it added
+struct PackageSnapshot {
+    manager: Box<dyn PackageManager>,
+    installed: Vec<InstalledPackage>,
+}
+
+impl PackageSnapshot {
+    /// Packages known to the native package manager, or `None` if there is none to ask
+    fn capture() -> Option<Self> {
+        let manager = os_detect::detect_distro()
+            .and_then(|distro| pkg::native_package_manager(&distro))
+            .ok()
+            .filter(|manager| manager.detect())?;
+        match manager.list_installed() {
+            Ok(installed) => Some(Self { manager, installed }),
+            Err(e) => {
+                debug!("Not recording packages added by the feature: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Packages added since the capture, along with the files owned by every
+    /// package that was added or upgraded
+    fn changes(&self) -> Result<(Vec<ReceiptPackage>, Vec<PathBuf>)> {
+        let after = self.manager.list_installed()?;
+        let added = package_manager::newly_installed(&self.installed, &after)
+            .into_iter()
+            .map(|name| ReceiptPackage {
+                manager: self.manager.name().to_string(),
+                name,
+            })
+            .collect();
+        let changed: Vec<String> = package_manager::changed(&self.installed, &after)
+            .into_iter()
+            .map(|package| package.name)
+            .collect();
+        Ok((added, self.manager.package_files(&changed)?))
+    }
+}
+
+fn record_receipt(
+    feature_ref: &str,
+    plan: &FeaturePlan,
+    files: Vec<PathBuf>,
+    packages_before: Option<&PackageSnapshot>,
+) -> Result<()> {
+    let mut receipt = Receipt::new(
+        &plan.feature.id,
+        "devcontainer-feature",
+        feature_ref,
+        receipt_version(plan),
+    );
+    receipt.options = receipt_options(plan);
+
+    // Files owned by packages go away when the packages are removed through their
+    // package manager, and deleting them directly would leave its database inconsistent
+    let (packages, package_files) = match packages_before {
+        Some(snapshot) => snapshot.changes()?,
+        None => (Vec::new(), Vec::new()),
+    };
+    receipt.packages = packages;
+    receipt.files = feature_files(files, &package_files)
+        .into_iter()
+        .map(|path| match ReceiptFile::hashed(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                debug!("Recording {} without a hash: {:#}", path.display(), e);
+                ReceiptFile { path, sha256: None }
+            }
+        })
+        .collect();
+    receipts::record(&receipt)
+}
+
+/// New files that belong to the feature rather than to scratch space, package
+/// manager databases or installed packages
+fn feature_files(files: Vec<PathBuf>, package_files: &[PathBuf]) -> Vec<PathBuf> {
+    // Package lists name paths as packaged, which may go through a symlinked
+    // directory such as `/lib` on merged-usr systems
+    let owned: HashSet<PathBuf> = package_files
+        .iter()
+        .flat_map(|path| [path.clone(), canonical_parent(path)])
+        .collect();
+
+    // Scratch files are gone by the time anyone uninstalls
+    let temp_dir = std::env::temp_dir();
+    files
+        .into_iter()
+        .filter(|path| !path.starts_with(&temp_dir))
+        .filter(|path| !package_manager::is_package_database(path))
+        .filter(|path| !owned.contains(path))
+        .collect()
+}
+
+/// The path with its parent directory resolved, leaving the file itself unresolved
+fn canonical_parent(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map_or_else(|_| path.to_path_buf(), |parent| parent.join(name)),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn receipt_version(plan: &FeaturePlan) -> &str {
+    plan.feature.version.as_deref().unwrap_or("unknown")
+}
+
+/// Resolved options along with the remote user, as recorded in the receipt
+fn receipt_options(plan: &FeaturePlan) -> BTreeMap<String, String> {
+    let mut options: BTreeMap<String, String> = plan.options.clone().into_iter().collect();
+    options.insert("remote_user".to_string(), plan.remote_user.clone());
+    options
 }
 
 #[cfg(test)]
@@ -443,6 +720,28 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    #[serial]
+    fn test_read_feature_metadata() {
+        let metadata = br#"{"id": "node", "version": "1.6.1", "options": {}}"#;
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(metadata.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "./devcontainer-feature.json", &metadata[..])
+            .unwrap();
+        let layer = builder.into_inner().unwrap().finish().unwrap();
+
+        let feature = read_feature_metadata(&layer).unwrap();
+        assert_eq!(feature.id, "node");
+        assert_eq!(feature.version.as_deref(), Some("1.6.1"));
+    }
+
     #[test]
     #[serial]
     fn test_resolve_options_with_defaults() {
@@ -499,4 +798,19 @@ mod tests {
         let resolved = resolve_options(&feature, Some(provided));
         assert_eq!(resolved.get("version"), Some(&"20".to_string()));
     }
+
+    #[test]
+    #[serial]
+    fn test_feature_files() {
+        let owned = PathBuf::from("/usr/lib/libfoo.so.1");
+        let written = PathBuf::from("/usr/local/bin/node");
+        let files = vec![
+            PathBuf::from("/var/lib/dpkg/info/libfoo.list"),
+            PathBuf::from("/var/cache/apt/pkgcache.bin"),
+            std::env::temp_dir().join("feature/install.sh"),
+            owned.clone(),
+            written.clone(),
+        ];
+        assert_eq!(feature_files(files, &[owned]), vec![written]);
+    }
 }
@@ -0,0 +1,309 @@
+use crate::installers::package_manager::{
+    self, InstalledPackage, PackageInstall, PackageManager, PackageManagerError, PlannedPackage,
+};
+use crate::utils;
+use crate::utils::command::{self, CommandError};
+use anyhow::Result;
+use log::debug;
+use std::path::PathBuf;
+
+/// Caches and rpm database leftovers removed after installing
+const DNF_CACHE_PATHS: &[&str] = &[
+    "/var/cache/dnf",
+    "/var/cache/yum",
+    "/var/lib/rpm/__db.*",
+    "/var/lib/rpmrebuilddb.*",
+];
+
+/// Install packages using dnf, microdnf or yum
+pub fn install(packages: &[String]) -> Result<PackageInstall> {
+    package_manager::install(&Dnf, packages)
+}
+
+/// dnf backend for Fedora and RHEL-like distributions
+#[derive(Debug, Default)]
+pub struct Dnf;
+
+impl PackageManager for Dnf {
+    fn name(&self) -> &'static str {
+        "dnf"
+    }
+
+    fn detect(&self) -> bool {
+        utils::os_detect::is_fedora_like() && dnf_binary().is_some()
+    }
+
+    fn unsupported_reason(&self) -> String {
+        "dnf should be used on Fedora and RHEL-like distributions (RHEL, CentOS, Rocky, AlmaLinux, etc.)".to_string()
+    }
+
+    fn update(&self) -> Result<()> {
+        command::run(dnf_makecache()).map_err(|e| PackageManagerError::Update("dnf", e))?;
+        Ok(())
+    }
+
+    fn install(&self, packages: &[String]) -> Result<()> {
+        debug!("Installing dnf packages: {:?}", packages);
+        command::run(dnf_install(packages)).map_err(|e| PackageManagerError::Install("dnf", e))?;
+        Ok(())
+    }
+
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        command::run(dnf_remove(packages)).map_err(|e| PackageManagerError::Purge("dnf", e))?;
+        Ok(())
+    }
+
+    fn clean(&self) -> Result<()> {
+        command::run(dnf_clean()).map_err(|e| PackageManagerError::Clean("dnf", e))?;
+        package_manager::remove_paths("dnf", DNF_CACHE_PATHS)
+    }
+
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
+        list_rpm_packages("dnf")
+    }
+
+    fn package_files(&self, packages: &[String]) -> Result<Vec<PathBuf>> {
+        list_rpm_files("dnf", packages)
+    }
+
+    fn plan(&self, packages: &[String]) -> Result<Vec<PlannedPackage>> {
+        // microdnf cannot stop before the transaction runs
+        if dnf_binary() == Some("microdnf") {
+            debug!("microdnf cannot simulate installs, listing the requested packages");
+            return Ok(packages
+                .iter()
+                .map(|p| PlannedPackage::requested(p))
+                .collect());
+        }
+
+        // --assumeno answers the confirmation prompt, so dnf exits with an error
+        // after printing the transaction
+        let stdout = match command::run(dnf_plan(packages)) {
+            Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+            Err(CommandError::Status { stdout, .. }) if stdout.contains("Transaction Summary") => {
+                stdout
+            }
+            Err(e) => return Err(PackageManagerError::Plan("dnf", e).into()),
+        };
+        Ok(parse_dnf_transaction(&stdout))
+    }
+}
+
+/// Pick microdnf on minimal images, otherwise dnf, falling back to yum
+fn dnf_binary() -> Option<&'static str> {
+    ["microdnf", "dnf", "yum"]
+        .into_iter()
+        .find(|binary| which::which(binary).is_ok())
+}
+
+/// List packages in the rpm database, shared by the rpm-based backends
+pub(crate) fn list_rpm_packages(manager: &'static str) -> Result<Vec<InstalledPackage>> {
+    let mut cmd = std::process::Command::new("rpm");
+    cmd.arg("-qa")
+        .arg("--queryformat")
+        .arg("%{NAME}\t%{VERSION}-%{RELEASE}\n");
+    let output = command::run(cmd).map_err(|e| PackageManagerError::List(manager, e))?;
+
+    Ok(package_manager::parse_installed(
+        &String::from_utf8_lossy(&output.stdout),
+        '\t',
+    ))
+}
+
+/// List files rpm installed for packages, shared by the rpm-based backends
+pub(crate) fn list_rpm_files(manager: &'static str, packages: &[String]) -> Result<Vec<PathBuf>> {
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut cmd = std::process::Command::new("rpm");
+    cmd.arg("-ql").args(packages);
+    let output = command::run(cmd).map_err(|e| PackageManagerError::Files(manager, e))?;
+
+    Ok(package_manager::parse_file_list(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+fn dnf() -> std::process::Command {
+    let mut cmd = std::process::Command::new("sudo");
+    cmd.arg(dnf_binary().unwrap_or("dnf"));
+    cmd
+}
+
+fn dnf_makecache() -> std::process::Command {
+    let mut cmd = dnf();
+    cmd.arg("makecache");
+    cmd
+}
+
+fn dnf_install(packages: &[String]) -> std::process::Command {
+    let mut cmd = dnf();
+    cmd.arg("install")
+        .arg("-y")
+        .arg("--setopt=install_weak_deps=False")
+        .args(packages);
+    cmd
+}
+
+fn dnf_plan(packages: &[String]) -> std::process::Command {
+    let mut cmd = dnf();
+    cmd.arg("install")
+        .arg("--assumeno")
+        .arg("--setopt=install_weak_deps=False")
+        .args(packages);
+    cmd
+}
+
+/// Parse the packages dnf would install or upgrade from its transaction table, e.g.
+/// ` tree   x86_64   1.8.0-10.fc38   fedora   56 k` under `Installing:`.
+/// dnf4 moves the columns of long package names to the next line
+fn parse_dnf_transaction(output: &str) -> Vec<PlannedPackage> {
+    let mut planned = Vec::new();
+    let mut in_section = false;
+    let mut wrapped_name: Option<&str> = None;
+
+    for line in output.lines() {
+        if !line.starts_with(' ') {
+            in_section = line.starts_with("Installing") || line.starts_with("Upgrading");
+            wrapped_name = None;
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+
+        let mut fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.first() == Some(&"replacing") {
+            continue;
+        }
+        if fields.len() == 1 {
+            wrapped_name = fields.first().copied();
+            continue;
+        }
+        if let Some(name) = wrapped_name.take() {
+            fields.insert(0, name);
+        }
+        // name, arch, version, repository, then the size and its unit
+        let [name, _arch, version, _repository, size, unit, ..] = fields.as_slice() else {
+            continue;
+        };
+        planned.push(PlannedPackage {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+            size: parse_dnf_size(size, unit),
+        });
+    }
+    planned
+}
+
+/// Parse sizes such as `56 k`, `1.2 M` (dnf4) or `110.2 KiB` (dnf5) into bytes
+fn parse_dnf_size(number: &str, unit: &str) -> Option<u64> {
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match unit {
+        "B" => 1.0,
+        "k" | "K" | "KiB" => 1024.0,
+        "M" | "MiB" => 1024.0 * 1024.0,
+        "G" | "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * multiplier) as u64)
+}
+
+fn dnf_remove(packages: &[String]) -> std::process::Command {
+    let mut cmd = dnf();
+    cmd.arg("remove").arg("-y").args(packages);
+    cmd
+}
+
+fn dnf_clean() -> std::process::Command {
+    let mut cmd = dnf();
+    cmd.arg("clean").arg("all");
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_dnf() {
+        let packages = vec!["curl".to_string()];
+        let _ = install(&packages);
+    }
+
+    #[test]
+    #[serial]
+    fn test_dnf_install_command() {
+        let cmd = dnf_install(&["curl".to_string()]);
+        let description = command::describe(&cmd);
+        assert!(description.contains("install -y --setopt=install_weak_deps=False curl"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_dnf_transaction() {
+        let dnf4 = "\
+Dependencies resolved.
+================================================================================
+ Package                   Architecture  Version            Repository     Size
+================================================================================
+Installing:
+ tree                      x86_64        1.8.0-10.fc38      fedora         56 k
+Upgrading:
+ curl                      x86_64        8.0.1-7.fc38       updates       348 k
+Installing dependencies:
+ python3-setuptools-wheel
+                           noarch        65.5.1-2.fc38      fedora        715 k
+
+Transaction Summary
+================================================================================
+Install  2 Packages
+Upgrade  1 Package
+";
+        assert_eq!(
+            parse_dnf_transaction(dnf4),
+            vec![
+                PlannedPackage {
+                    name: "tree".to_string(),
+                    version: Some("1.8.0-10.fc38".to_string()),
+                    size: Some(56 * 1024),
+                },
+                PlannedPackage {
+                    name: "curl".to_string(),
+                    version: Some("8.0.1-7.fc38".to_string()),
+                    size: Some(348 * 1024),
+                },
+                PlannedPackage {
+                    name: "python3-setuptools-wheel".to_string(),
+                    version: Some("65.5.1-2.fc38".to_string()),
+                    size: Some(715 * 1024),
+                },
+            ]
+        );
+
+        let dnf5 = "\
+Package  Arch   Version      Repository      Size
+Installing:
+ tree    x86_64 2.1.1-2.fc40 fedora     110.5 KiB
+Upgrading:
+ curl    x86_64 8.6.0-8.fc40 updates    793.5 KiB
+   replacing curl x86_64 8.6.0-7.fc40 fedora 793.5 KiB
+
+Transaction Summary:
+ Installing:  1 package
+";
+        let planned = parse_dnf_transaction(dnf5);
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].size, Some(113152));
+        assert_eq!(planned[1].version.as_deref(), Some("8.6.0-8.fc40"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_dnf_plan_command() {
+        let description = command::describe(&dnf_plan(&["curl".to_string()]));
+        assert!(description.contains("install --assumeno --setopt=install_weak_deps=False curl"));
+    }
+}
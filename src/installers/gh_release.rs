@@ -1,34 +1,98 @@
+use crate::installers::session::Session;
+use crate::utils::download::{self, Download, Downloader};
+use crate::utils::github::{self, Token};
+use crate::utils::receipts::{self, Receipt, ReceiptFile};
+use crate::utils::snapshot::format_size;
 use anyhow::{Context, Result};
+use bzip2::read::BzDecoder;
+use clap::ValueEnum;
 use flate2::read::GzDecoder;
 use log::{info, warn};
 use regex::Regex;
+use reqwest::Url;
 use reqwest::blocking::Client;
-use serde::Deserialize;
-use sha2::{Digest, Sha256};
+use semver::{Prerelease, Version, VersionReq};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs::{self, File};
-
-use std::io::{BufReader, Write};
-use std::path::Path;
+use std::io::{self, BufReader, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
 
 use tar::Archive;
 use xz::read::XzDecoder;
 
-const GITHUB_API: &str = "api.github.com";
+const GITHUB_API: &str = "https://api.github.com";
+
+const GITLAB_API: &str = "https://gitlab.com/api/v4";
+
+/// Source recorded in the receipts of release installs
+pub const RECEIPT_SOURCE: &str = "gh-release";
+
+/// Highest number of release pages (100 releases each) searched for a version range
+const MAX_RELEASE_PAGES: usize = 10;
 
 #[derive(Debug, Deserialize)]
 struct Release {
     tag_name: String,
     assets: Vec<Asset>,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    draft: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct Asset {
     name: String,
     browser_download_url: String,
+    #[serde(default)]
+    size: u64,
+}
+
+/// Forge whose release API serves the repository
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    /// GitHub or GitHub Enterprise Server
+    #[default]
+    Github,
+    /// Gitea or Forgejo
+    #[serde(alias = "forgejo")]
+    #[value(alias = "forgejo")]
+    Gitea,
+    Gitlab,
+}
+
+impl Forge {
+    /// Environment variables holding a token for this forge, or for GitHub Enterprise
+    /// when `api_url` is not GitHub. GitHub tokens are never read for other hosts,
+    /// so they cannot leak to them
+    fn token_vars(self, api_url: Option<&str>) -> &'static [&'static str] {
+        match self {
+            Self::Github if api_url.is_some_and(|url| !github::is_github_url(url)) => {
+                github::ENTERPRISE_TOKEN_VARS
+            }
+            Self::Github => github::TOKEN_VARS,
+            Self::Gitea => &["GITEA_TOKEN", "FORGEJO_TOKEN"],
+            Self::Gitlab => &["GITLAB_TOKEN"],
+        }
+    }
+}
+
+impl fmt::Display for Forge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Github => "github",
+            Self::Gitea => "gitea",
+            Self::Gitlab => "gitlab",
+        })
+    }
 }
 
 /// Configuration for installing a GitHub release
 pub struct GhReleaseConfig<'a> {
+    /// Repository, or the project path on GitLab (e.g. `group/subgroup/project`)
     pub repo: &'a str,
     pub binary_names: &'a [String],
     pub version: &'a str,
@@ -37,120 +101,824 @@ pub struct GhReleaseConfig<'a> {
     pub verify_checksum: bool,
     pub checksum_text: Option<&'a str>,
     pub gpg_key: Option<&'a str>,
+    /// Regex extracting the version from tags, from the `version` group or the first group
+    /// (e.g. `^cli-v(.+)$`); tags that do not match are ignored when resolving a range
+    pub tag_regex: Option<&'a str>,
+    /// Consider prereleases and drafts when resolving `latest` or a version range
+    pub include_prereleases: bool,
+    /// File holding an API token, read instead of `GITHUB_TOKEN`/`GH_TOKEN`,
+    /// `GH_ENTERPRISE_TOKEN`/`GITHUB_ENTERPRISE_TOKEN`, `GITEA_TOKEN`/`FORGEJO_TOKEN`
+    /// or `GITLAB_TOKEN` depending on the forge
+    pub token_file: Option<&'a str>,
+    /// Forge serving the releases
+    pub source: Forge,
+    /// API of a self-hosted forge (e.g. `https://ghe.example.com/api/v3`); a bare host
+    /// gets the forge's API path appended
+    pub api_url: Option<&'a str>,
+    /// Reinstall even if the requested version is already installed
+    pub force: bool,
 }
 
-/// Install binaries from a GitHub release
-pub fn install(input: &GhReleaseConfig) -> Result<()> {
-    Installer::new().install(input)
+/// How `GhReleaseConfig::version` selects a release
+#[derive(Debug, PartialEq)]
+enum VersionSpec<'a> {
+    Latest,
+    Tag(&'a str),
+    Range(VersionReq),
+}
+
+impl<'a> VersionSpec<'a> {
+    /// Versions starting with a comparison operator or containing a wildcard or comma are
+    /// ranges, anything else is an exact tag
+    fn parse(version: &'a str) -> Result<Self> {
+        if version == "latest" {
+            return Ok(Self::Latest);
+        }
+        let is_range = version.starts_with(['^', '~', '<', '>', '=', '*'])
+            || version.contains([',', '*'])
+            || version.ends_with(".x")
+            || version.ends_with(".X");
+        if !is_range {
+            return Ok(Self::Tag(version));
+        }
+        VersionReq::parse(version)
+            .map(Self::Range)
+            .with_context(|| format!("Invalid version range: {}", version))
+    }
+
+    fn is_tag(&self) -> bool {
+        matches!(self, Self::Tag(_))
+    }
 }
 
-struct Installer {
-    client: Client,
+/// How a selected release asset is verified before installing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "name", rename_all = "snake_case")]
+pub enum ChecksumSource {
+    None,
+    /// Hash given on the command line as `algorithm:hash`
+    Text(String),
+    /// Checksum file published with the release
+    File(String),
+    /// Detached signature checked against the given GPG key
+    Signature(String),
+    /// Detached signature that is skipped because no GPG key was given
+    UnverifiedSignature(String),
 }
 
-impl Installer {
-    fn new() -> Self {
-        Self {
-            client: Client::new(),
+impl fmt::Display for ChecksumSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChecksumSource::None => write!(f, "not verified"),
+            ChecksumSource::Text(text) => write!(f, "{}", text),
+            ChecksumSource::File(name) => write!(f, "sha256 from {}", name),
+            ChecksumSource::Signature(name) => write!(f, "GPG signature {}", name),
+            ChecksumSource::UnverifiedSignature(name) => {
+                write!(f, "GPG signature {} (skipped, no GPG key)", name)
+            }
         }
     }
+}
 
-    fn install(&self, config: &GhReleaseConfig) -> Result<()> {
-        info!("Fetching release information for {}", config.repo);
+/// The release asset an install would download and the binaries it would write
+#[derive(Debug, Serialize)]
+pub struct ReleasePlan {
+    pub repo: String,
+    pub tag: String,
+    pub checksum: ChecksumSource,
+    pub binaries: Vec<PathBuf>,
+    asset: Asset,
+    #[serde(skip)]
+    checksum_asset: Option<Asset>,
+}
 
-        let release = self.fetch_release(config.repo, config.version)?;
-        info!("Installing from release: {}", release.tag_name);
+/// A release installed from GitHub
+#[derive(Debug, Serialize)]
+pub struct ReleaseInstall {
+    pub repo: String,
+    pub tag: String,
+    /// Downloaded asset, if known
+    pub asset: Option<String>,
+    /// Verified hash as `algorithm:hash`
+    pub checksum: Option<String>,
+    /// Verified detached signature file
+    pub signature: Option<String>,
+    pub binaries: Vec<PathBuf>,
+    /// Why nothing was downloaded, when the version was already installed
+    pub skipped: Option<String>,
+}
 
-        let gpg_verification = config.verify_checksum && config.gpg_key.is_some();
-        let asset = self.select_asset(&release.assets, config.filter, gpg_verification)?;
-        info!("Selected asset: {}", asset.name);
+impl ReleasePlan {
+    pub fn asset_name(&self) -> &str {
+        &self.asset.name
+    }
 
-        if let Some(checksum_text) = config.checksum_text {
-            self.verify_asset_with_checksum_text(asset, checksum_text)?;
-        } else if config.verify_checksum {
-            self.verify_asset(&release.assets, asset, config.gpg_key)?;
+    pub fn asset_url(&self) -> &str {
+        &self.asset.browser_download_url
+    }
+}
+
+impl fmt::Display for ReleasePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "gh-release would install {} {}:", self.repo, self.tag)?;
+        writeln!(
+            f,
+            "  asset: {} ({})",
+            self.asset.name,
+            format_size(self.asset.size as i64)
+        )?;
+        writeln!(f, "  url: {}", self.asset.browser_download_url)?;
+        writeln!(f, "  checksum: {}", self.checksum)?;
+        for binary in &self.binaries {
+            writeln!(f, "  binary: {}", binary.display())?;
         }
+        Ok(())
+    }
+}
 
-        self.download_and_install_asset(asset, config.binary_names, config.install_dir)?;
+/// Resolve the release asset and checksum source without downloading the asset
+pub fn plan(input: &GhReleaseConfig) -> Result<ReleasePlan> {
+    let token = resolve_token(input)?;
+    plan_release(&Client::new(), token.as_ref(), input)
+}
 
-        info!("Installation complete!");
-        Ok(())
+/// Install binaries from a GitHub release
+pub fn install(input: &GhReleaseConfig) -> Result<ReleaseInstall> {
+    install_in(&Session::new()?, input)
+}
+
+/// Install binaries from a GitHub release using a shared session
+pub fn install_in(session: &Session, input: &GhReleaseConfig) -> Result<ReleaseInstall> {
+    // A pinned tag can be checked before asking GitHub which release it is
+    let exact_tag = VersionSpec::parse(input.version)?.is_tag();
+    if !input.force
+        && exact_tag
+        && let Some(skipped) = already_installed(input, input.version)
+    {
+        return Ok(skipped);
     }
 
-    fn fetch_release(&self, repo: &str, version: &str) -> Result<Release> {
-        ReleaseClient::new(&self.client).fetch(repo, version)
+    let token = resolve_token(input)?;
+    let plan = plan_release(session.client(), token.as_ref(), input)?;
+    if !input.force
+        && !exact_tag
+        && let Some(skipped) = already_installed(input, &plan.tag)
+    {
+        return Ok(skipped);
     }
 
-    fn select_asset<'a>(
-        &self,
-        assets: &'a [Asset],
-        filter: Option<&str>,
-        gpg_verification: bool,
-    ) -> Result<&'a Asset> {
-        let selector = AssetSelector::new();
-        if gpg_verification {
-            selector.select_with_signature(assets, filter)
-        } else {
-            selector.select(assets, filter)
+    let installed = Installer::new(session, token.as_ref()).install(plan, input)?;
+    if let Err(e) = record_receipt(&installed, input) {
+        warn!("Failed to record receipt for {}: {:#}", input.repo, e);
+    }
+    Ok(installed)
+}
+
+fn record_receipt(installed: &ReleaseInstall, config: &GhReleaseConfig) -> Result<()> {
+    let mut receipt = Receipt::new(
+        &installed.repo,
+        RECEIPT_SOURCE,
+        &installed.repo,
+        &installed.tag,
+    );
+    receipt.checksum = installed.checksum.clone();
+    if let Some(asset) = &installed.asset {
+        receipt
+            .options
+            .insert("asset".to_string(), asset.to_string());
+    }
+    receipt
+        .options
+        .insert("binary_names".to_string(), config.binary_names.join(","));
+    receipt
+        .options
+        .insert("install_dir".to_string(), config.install_dir.to_string());
+    if let Some(filter) = config.filter {
+        receipt
+            .options
+            .insert("filter".to_string(), filter.to_string());
+    }
+    if config.verify_checksum {
+        receipt
+            .options
+            .insert("verify_checksum".to_string(), "true".to_string());
+    }
+    if let Some(gpg_key) = config.gpg_key {
+        receipt
+            .options
+            .insert("gpg_key".to_string(), gpg_key.to_string());
+    }
+    if let Some(tag_regex) = config.tag_regex {
+        receipt
+            .options
+            .insert("tag_regex".to_string(), tag_regex.to_string());
+    }
+    if config.include_prereleases {
+        receipt
+            .options
+            .insert("include_prereleases".to_string(), "true".to_string());
+    }
+    if config.source != Forge::Github {
+        receipt
+            .options
+            .insert("source".to_string(), config.source.to_string());
+    }
+    if let Some(api_url) = config.api_url {
+        receipt
+            .options
+            .insert("api_url".to_string(), api_url.to_string());
+    }
+    receipt.options.insert(
+        "verification".to_string(),
+        verification(installed, config.checksum_text.is_some()).to_string(),
+    );
+    receipt.files = installed
+        .binaries
+        .iter()
+        .map(|binary| ReceiptFile::hashed(binary))
+        .collect::<Result<_>>()?;
+    receipts::record(&receipt)
+}
+
+/// How the downloaded asset was actually verified: `gpg`, `checksum`, `checksum-text`
+/// for a checksum given on the command line, or `none`
+fn verification(installed: &ReleaseInstall, checksum_text: bool) -> &'static str {
+    match (&installed.signature, &installed.checksum) {
+        (Some(_), _) => "gpg",
+        (None, Some(_)) if checksum_text => "checksum-text",
+        (None, Some(_)) => "checksum",
+        (None, None) => "none",
+    }
+}
+
+/// Report the install as skipped when the binaries of `tag` are already in place,
+/// according to the receipt or, without one, to `<binary> --version`
+fn already_installed(config: &GhReleaseConfig, tag: &str) -> Option<ReleaseInstall> {
+    let binaries: Vec<PathBuf> = config
+        .binary_names
+        .iter()
+        .map(|name| Path::new(config.install_dir).join(name))
+        .collect();
+    if !binaries.iter().all(|binary| binary.is_file()) {
+        return None;
+    }
+
+    let receipt = receipts::get(config.repo).ok().flatten();
+    let satisfied = match &receipt {
+        Some(receipt) => {
+            same_version(&receipt.version, tag)
+                && binaries
+                    .iter()
+                    .all(|binary| receipt.files.iter().any(|file| &file.path == binary))
+                && receipt.files_unchanged()
         }
+        None => binaries.iter().all(|binary| reports_version(binary, tag)),
+    };
+    if !satisfied {
+        return None;
     }
 
-    fn verify_asset(&self, assets: &[Asset], asset: &Asset, gpg_key: Option<&str>) -> Result<()> {
-        AssetVerifier::new(&self.client).verify(assets, asset, gpg_key)
+    let reason = format!(
+        "{} {} is already installed (use --force to reinstall)",
+        config.repo, tag
+    );
+    info!("{}", reason);
+    Some(ReleaseInstall {
+        repo: config.repo.to_string(),
+        tag: receipt
+            .as_ref()
+            .map_or_else(|| tag.to_string(), |r| r.version.clone()),
+        asset: receipt
+            .as_ref()
+            .and_then(|r| r.options.get("asset").cloned()),
+        checksum: receipt.and_then(|r| r.checksum),
+        signature: None,
+        binaries,
+        skipped: Some(reason),
+    })
+}
+
+/// Compare release tags, ignoring a leading `v`
+fn same_version(a: &str, b: &str) -> bool {
+    a.trim_start_matches('v') == b.trim_start_matches('v')
+}
+
+/// Whether `<binary> --version` prints the version of `tag`
+fn reports_version(binary: &Path, tag: &str) -> bool {
+    let Ok(output) = std::process::Command::new(binary)
+        .arg("--version")
+        .stdin(std::process::Stdio::null())
+        .output()
+    else {
+        return false;
+    };
+    mentions_version(&String::from_utf8_lossy(&output.stdout), tag)
+        || mentions_version(&String::from_utf8_lossy(&output.stderr), tag)
+}
+
+/// Whether version output contains the version of `tag` as a whole word
+fn mentions_version(output: &str, tag: &str) -> bool {
+    output
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')' | '"' | '\''))
+        .any(|word| same_version(word, tag))
+}
+
+struct Installer<'s> {
+    client: &'s Client,
+    token: Option<&'s Token>,
+    temp_dir: &'s Path,
+}
+
+impl<'s> Installer<'s> {
+    fn new(session: &'s Session, token: Option<&'s Token>) -> Self {
+        Self {
+            client: session.client(),
+            token,
+            temp_dir: session.temp_dir(),
+        }
     }
 
-    fn verify_asset_with_checksum_text(&self, asset: &Asset, checksum_text: &str) -> Result<()> {
-        AssetVerifier::new(&self.client).verify_with_checksum_text(asset, checksum_text)
+    fn install(&self, plan: ReleasePlan, config: &GhReleaseConfig) -> Result<ReleaseInstall> {
+        info!("Installing from release: {}", plan.tag);
+
+        let download_dir = tempfile::tempdir_in(self.temp_dir)?;
+        let download = self.download_asset(&plan.asset, download_dir.path())?;
+
+        let checksum = if let ChecksumSource::Text(checksum_text) = &plan.checksum {
+            Some(self.verify_asset_with_checksum_text(&download, checksum_text)?)
+        } else if let Some(checksum_asset) = &plan.checksum_asset {
+            self.verify_asset(&plan.asset, &download, checksum_asset, config.gpg_key)?
+        } else {
+            None
+        };
+
+        let binaries =
+            self.install_asset(&download.path, config.binary_names, config.install_dir)?;
+
+        info!("Installation complete!");
+        Ok(ReleaseInstall {
+            repo: plan.repo,
+            tag: plan.tag,
+            asset: Some(plan.asset.name),
+            checksum,
+            signature: match plan.checksum {
+                ChecksumSource::Signature(name) => Some(name),
+                _ => None,
+            },
+            binaries,
+            skipped: None,
+        })
     }
 
-    fn download_and_install_asset(
+    /// Download the asset once; verification and extraction both read this file.
+    /// The file is named after the URL, as asset names on some forges are free-form
+    fn download_asset(&self, asset: &Asset, dir: &Path) -> Result<Download> {
+        info!("Downloading asset");
+        let dest = dir.join(download::file_name(&asset.browser_download_url));
+        Downloader::new(self.client)
+            .github_token(self.token)
+            .download(&asset.browser_download_url, &dest)
+            .context("Failed to download asset")
+    }
+
+    fn verify_asset(
         &self,
         asset: &Asset,
+        download: &Download,
+        checksum_asset: &Asset,
+        gpg_key: Option<&str>,
+    ) -> Result<Option<String>> {
+        AssetVerifier::new(self.client, self.token).verify(asset, download, checksum_asset, gpg_key)
+    }
+
+    fn verify_asset_with_checksum_text(
+        &self,
+        download: &Download,
+        checksum_text: &str,
+    ) -> Result<String> {
+        AssetVerifier::new(self.client, self.token)
+            .verify_with_checksum_text(download, checksum_text)
+    }
+
+    fn install_asset(
+        &self,
+        asset_path: &Path,
         binary_names: &[String],
         bin_location: &str,
-    ) -> Result<()> {
-        AssetInstaller::new(&self.client).install(asset, binary_names, bin_location)
+    ) -> Result<Vec<PathBuf>> {
+        info!("Extracting binaries: {}", binary_names.join(", "));
+        AssetInstaller::new()
+            .in_temp_dir(self.temp_dir)
+            .extract_binaries(asset_path, binary_names, bin_location)
     }
 }
 
-struct ReleaseClient<'a> {
+fn plan_release(
+    client: &Client,
+    token: Option<&Token>,
+    config: &GhReleaseConfig,
+) -> Result<ReleasePlan> {
+    info!("Fetching release information for {}", config.repo);
+    let release = fetch_release(client, token, config)?;
+
+    let selector = AssetSelector::new();
+    let asset = if config.verify_checksum && config.gpg_key.is_some() {
+        selector.select_with_signature(&release.assets, config.filter)?
+    } else {
+        selector.select(&release.assets, config.filter)?
+    };
+    info!("Selected asset: {}", asset.name);
+
+    let checksum_asset = match config.checksum_text {
+        None if config.verify_checksum => Some(
+            AssetVerifier::new(client, token)
+                .find_checksum_asset(&release.assets, asset)?
+                .clone(),
+        ),
+        _ => None,
+    };
+    let checksum = match (config.checksum_text, &checksum_asset) {
+        (Some(text), _) => ChecksumSource::Text(text.to_string()),
+        (None, Some(c)) if is_signature(&c.name) && config.gpg_key.is_some() => {
+            ChecksumSource::Signature(c.name.clone())
+        }
+        (None, Some(c)) if is_signature(&c.name) => {
+            ChecksumSource::UnverifiedSignature(c.name.clone())
+        }
+        (None, Some(c)) => ChecksumSource::File(c.name.clone()),
+        (None, None) => ChecksumSource::None,
+    };
+
+    Ok(ReleasePlan {
+        repo: config.repo.to_string(),
+        tag: release.tag_name.clone(),
+        checksum,
+        binaries: config
+            .binary_names
+            .iter()
+            .map(|name| Path::new(config.install_dir).join(name))
+            .collect(),
+        asset: asset.clone(),
+        checksum_asset,
+    })
+}
+
+/// Tag of the release the configured version selects
+pub fn resolve_tag(client: &Client, config: &GhReleaseConfig) -> Result<String> {
+    let token = resolve_token(config)?;
+    Ok(fetch_release(client, token.as_ref(), config)?.tag_name)
+}
+
+fn fetch_release(
+    client: &Client,
+    token: Option<&Token>,
+    config: &GhReleaseConfig,
+) -> Result<Release> {
+    let releases = release_source(client, token, config)?;
+    let req = match VersionSpec::parse(config.version)? {
+        VersionSpec::Tag(tag) => return releases.fetch(config.repo, tag),
+        // GitHub's latest release skips prereleases and may belong to another tag series
+        VersionSpec::Latest if !config.include_prereleases && config.tag_regex.is_none() => {
+            return releases.fetch(config.repo, "latest");
+        }
+        VersionSpec::Latest => VersionReq::STAR,
+        VersionSpec::Range(req) => req,
+    };
+
+    let tag_regex = config
+        .tag_regex
+        .map(Regex::new)
+        .transpose()
+        .context("Invalid tag regex")?;
+    let candidates = releases.list(config.repo)?;
+    let release = select_release(
+        candidates,
+        &req,
+        tag_regex.as_ref(),
+        config.include_prereleases,
+    )
+    .with_context(|| {
+        format!(
+            "No release of {} matches version {}",
+            config.repo, config.version
+        )
+    })?;
+    info!("Resolved {} to {}", config.version, release.tag_name);
+    Ok(release)
+}
+
+/// The release with the highest version matching `req`
+fn select_release(
+    releases: Vec<Release>,
+    req: &VersionReq,
+    tag_regex: Option<&Regex>,
+    include_prereleases: bool,
+) -> Option<Release> {
+    releases
+        .into_iter()
+        .filter(|release| include_prereleases || !(release.prerelease || release.draft))
+        .filter_map(|release| {
+            let version = tag_version(&release.tag_name, tag_regex)?;
+            // Prereleases match a range as the release they precede would
+            let matches = req.matches(&version)
+                || (include_prereleases
+                    && req.matches(&Version {
+                        pre: Prerelease::EMPTY,
+                        ..version.clone()
+                    }));
+            (matches && (include_prereleases || version.pre.is_empty()))
+                .then_some((version, release))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, release)| release)
+}
+
+/// Version of a tag, stripping a leading `v` or extracting it with the tag regex,
+/// and padding versions such as `1.2` to `1.2.0`
+fn tag_version(tag: &str, tag_regex: Option<&Regex>) -> Option<Version> {
+    let version = match tag_regex {
+        Some(regex) => {
+            let captures = regex.captures(tag)?;
+            captures
+                .name("version")
+                .or_else(|| captures.get(1))
+                .or_else(|| captures.get(0))?
+                .as_str()
+        }
+        None => tag.strip_prefix('v').unwrap_or(tag),
+    };
+
+    let (core, suffix) = version
+        .find(['-', '+'])
+        .map_or((version, ""), |at| version.split_at(at));
+    let padding = match core.split('.').count() {
+        1 => ".0.0",
+        2 => ".0",
+        _ => "",
+    };
+    Version::parse(&format!("{}{}{}", core, padding, suffix)).ok()
+}
+
+/// Token for the configured forge, read from that forge's variables. It is sent to
+/// GitHub's own hosts for github.com, otherwise only to the host of the forge's API
+fn resolve_token(config: &GhReleaseConfig) -> Result<Option<Token>> {
+    let vars = config.source.token_vars(config.api_url);
+    let Some(token) = Token::resolve(config.token_file, vars)? else {
+        return Ok(None);
+    };
+    if config.source == Forge::Github && config.api_url.is_none_or(github::is_github_url) {
+        return Ok(Some(token));
+    }
+    let base = api_base(config.source, config.api_url)?;
+    let host = base
+        .host_str()
+        .with_context(|| format!("API URL has no host: {}", base))?;
+    Ok(Some(token.for_host(host)))
+}
+
+fn is_signature(name: &str) -> bool {
+    name.ends_with(".asc") || name.ends_with(".sig")
+}
+
+/// Release API of a forge, returning releases in GitHub's shape
+trait ReleaseSource {
+    /// The latest release when `version` is `latest`, otherwise the release tagged `version`
+    fn fetch(&self, repo: &str, version: &str) -> Result<Release>;
+
+    /// Releases of a repository, newest first, up to `MAX_RELEASE_PAGES` pages
+    fn list(&self, repo: &str) -> Result<Vec<Release>>;
+}
+
+/// Release source for `config`, talking to its API URL or the forge's public instance
+fn release_source<'a>(
     client: &'a Client,
+    token: Option<&'a Token>,
+    config: &GhReleaseConfig,
+) -> Result<Box<dyn ReleaseSource + 'a>> {
+    let api = Api {
+        client,
+        token,
+        base: api_base(config.source, config.api_url)?,
+    };
+    Ok(match config.source {
+        Forge::Github => Box::new(GitHubSource::github(api)),
+        Forge::Gitea => Box::new(GitHubSource::gitea(api)),
+        Forge::Gitlab => Box::new(GitLabSource(api)),
+    })
 }
 
-impl<'a> ReleaseClient<'a> {
-    fn new(client: &'a Client) -> Self {
-        Self { client }
+/// Base URL of the forge's API. A bare host gets the API path of its forge,
+/// e.g. `https://ghe.example.com` becomes `https://ghe.example.com/api/v3`
+fn api_base(forge: Forge, api_url: Option<&str>) -> Result<Url> {
+    let (default, api_path) = match forge {
+        Forge::Github => (Some(GITHUB_API), "api/v3"),
+        Forge::Gitea => (None, "api/v1"),
+        Forge::Gitlab => (Some(GITLAB_API), "api/v4"),
+    };
+    let Some(api_url) = api_url else {
+        let default =
+            default.with_context(|| format!("--api-url is required for {} releases", forge))?;
+        return Ok(Url::parse(default)?);
+    };
+
+    let mut base = Url::parse(api_url).with_context(|| format!("Invalid API URL: {}", api_url))?;
+    if base.path() == "/" {
+        base.set_path(api_path);
     }
+    Ok(base)
+}
 
-    fn fetch(&self, repo: &str, version: &str) -> Result<Release> {
-        let url = self.build_url(repo, version);
+/// Releases pages of `per_page` entries until a short page or `MAX_RELEASE_PAGES`
+fn list_pages(
+    repo: &str,
+    per_page: usize,
+    fetch_page: impl Fn(usize) -> Result<Vec<Release>>,
+) -> Result<Vec<Release>> {
+    let mut releases = Vec::new();
+
+    for page in 1..=MAX_RELEASE_PAGES {
+        let batch = fetch_page(page)?;
+        let last_page = batch.len() < per_page;
+        releases.extend(batch);
+        if last_page {
+            return Ok(releases);
+        }
+    }
 
-        let response = self
-            .client
-            .get(&url)
-            .header("User-Agent", "picolayer")
-            .send()
-            .context("Failed to fetch release information")?;
+    warn!(
+        "Only the {} most recent releases of {} were searched",
+        releases.len(),
+        repo
+    );
+    Ok(releases)
+}
+
+/// HTTP access to a forge API below `base`
+struct Api<'a> {
+    client: &'a Client,
+    token: Option<&'a Token>,
+    base: Url,
+}
+
+impl Api<'_> {
+    /// URL of the API path made of `segments`, each percent-encoded as one segment
+    fn url<'s>(&self, segments: impl IntoIterator<Item = &'s str>) -> Url {
+        let mut url = self.base.clone();
+        if let Ok(mut path) = url.path_segments_mut() {
+            path.pop_if_empty().extend(segments);
+        }
+        url
+    }
+
+    /// GET an API URL with the token, waiting out short rate limits
+    fn get<T: DeserializeOwned>(&self, url: Url, what: &str) -> Result<T> {
+        let response = github::send(
+            || {
+                let request = self
+                    .client
+                    .get(url.clone())
+                    .header("User-Agent", "picolayer");
+                github::authorize(request, url.as_str(), self.token)
+            },
+            self.token.is_some(),
+        )
+        .with_context(|| format!("Failed to fetch {}", what))?;
 
         if !response.status().is_success() {
-            anyhow::bail!("Failed to fetch release: {}", response.status());
+            anyhow::bail!("Failed to fetch {}: {}", what, response.status());
         }
 
-        response.json().context("Failed to parse release JSON")
+        response
+            .json()
+            .with_context(|| format!("Failed to parse {} JSON", what))
     }
+}
 
-    fn build_url(&self, repo: &str, version: &str) -> String {
-        if version == "latest" {
-            format!("https://{}/repos/{}/releases/latest", GITHUB_API, repo)
+/// GitHub, GitHub Enterprise Server, and Gitea and Forgejo, whose release API
+/// mirrors GitHub's apart from paging
+struct GitHubSource<'a> {
+    api: Api<'a>,
+    per_page_param: &'static str,
+    per_page: usize,
+}
+
+impl<'a> GitHubSource<'a> {
+    fn github(api: Api<'a>) -> Self {
+        Self {
+            api,
+            per_page_param: "per_page",
+            per_page: 100,
+        }
+    }
+
+    /// Gitea caps pages at 50 entries by default
+    fn gitea(api: Api<'a>) -> Self {
+        Self {
+            api,
+            per_page_param: "limit",
+            per_page: 50,
+        }
+    }
+
+    fn releases_url(&self, repo: &str, rest: &[&str]) -> Url {
+        let segments = ["repos"].into_iter().chain(repo.split('/'));
+        self.api
+            .url(segments.chain(["releases"]).chain(rest.iter().copied()))
+    }
+}
+
+impl ReleaseSource for GitHubSource<'_> {
+    fn fetch(&self, repo: &str, version: &str) -> Result<Release> {
+        let url = if version == "latest" {
+            self.releases_url(repo, &["latest"])
         } else {
-            format!(
-                "https://{}/repos/{}/releases/tags/{}",
-                GITHUB_API, repo, version
-            )
+            self.releases_url(repo, &["tags", version])
+        };
+        self.api.get(url, "release")
+    }
+
+    fn list(&self, repo: &str) -> Result<Vec<Release>> {
+        list_pages(repo, self.per_page, |page| {
+            let mut url = self.releases_url(repo, &[]);
+            url.query_pairs_mut()
+                .append_pair(self.per_page_param, &self.per_page.to_string())
+                .append_pair("page", &page.to_string());
+            self.api.get(url, "releases")
+        })
+    }
+}
+
+/// GitLab, addressing projects by their full path
+struct GitLabSource<'a>(Api<'a>);
+
+#[derive(Debug, Deserialize)]
+struct GitLabRelease {
+    tag_name: String,
+    #[serde(default)]
+    upcoming_release: bool,
+    assets: GitLabAssets,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabAssets {
+    #[serde(default)]
+    links: Vec<GitLabLink>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabLink {
+    name: String,
+    url: String,
+    direct_asset_url: Option<String>,
+}
+
+impl From<GitLabRelease> for Release {
+    /// Release links become assets; the generated source archives are left out
+    fn from(release: GitLabRelease) -> Self {
+        Release {
+            tag_name: release.tag_name,
+            assets: release
+                .assets
+                .links
+                .into_iter()
+                .map(|link| Asset {
+                    name: link.name,
+                    browser_download_url: link.direct_asset_url.unwrap_or(link.url),
+                    size: 0,
+                })
+                .collect(),
+            prerelease: release.upcoming_release,
+            draft: false,
         }
     }
 }
 
+impl ReleaseSource for GitLabSource<'_> {
+    fn fetch(&self, repo: &str, version: &str) -> Result<Release> {
+        let url = if version == "latest" {
+            self.0
+                .url(["projects", repo, "releases", "permalink", "latest"])
+        } else {
+            self.0.url(["projects", repo, "releases", version])
+        };
+        self.0
+            .get::<GitLabRelease>(url, "release")
+            .map(Release::from)
+    }
+
+    fn list(&self, repo: &str) -> Result<Vec<Release>> {
+        const PER_PAGE: usize = 100;
+        list_pages(repo, PER_PAGE, |page| {
+            let mut url = self.0.url(["projects", repo, "releases"]);
+            url.query_pairs_mut()
+                .append_pair("per_page", &PER_PAGE.to_string())
+                .append_pair("page", &page.to_string());
+            let releases: Vec<GitLabRelease> = self.0.get(url, "releases")?;
+            Ok(releases.into_iter().map(Release::from).collect())
+        })
+    }
+}
+
 struct AssetSelector;
 
 impl AssetSelector {
@@ -221,6 +989,7 @@ impl AssetSelector {
             .context("No asset matching filter pattern")
     }
 
+    /// Prefer an archive for this platform, falling back to a bare binary such as `jq-linux-amd64`
     fn select_by_platform<'a>(&self, assets: &'a [Asset]) -> Option<&'a Asset> {
         let arch = std::env::consts::ARCH;
         let os = std::env::consts::OS;
@@ -228,18 +997,25 @@ impl AssetSelector {
         let arch_patterns = self.get_arch_patterns(arch);
         let os_patterns = self.get_os_patterns(os);
 
-        assets.iter().find(|asset| {
-            let name_lower = asset.name.to_lowercase();
-            let has_arch = arch_patterns
-                .iter()
-                .any(|p| name_lower.contains(&p.to_lowercase()));
-            let has_os = os_patterns
-                .iter()
-                .any(|p| name_lower.contains(&p.to_lowercase()));
-            let is_archive = self.is_archive(&name_lower);
+        let platform_assets = || {
+            assets.iter().filter(|asset| {
+                let name_lower = asset.name.to_lowercase();
+                let has_arch = arch_patterns
+                    .iter()
+                    .any(|p| name_lower.contains(&p.to_lowercase()));
+                let has_os = os_patterns
+                    .iter()
+                    .any(|p| name_lower.contains(&p.to_lowercase()));
+
+                has_arch && has_os
+            })
+        };
 
-            has_arch && has_os && is_archive
-        })
+        platform_assets()
+            .find(|asset| self.is_archive(&asset.name.to_lowercase()))
+            .or_else(|| {
+                platform_assets().find(|asset| self.is_bare_binary(&asset.name.to_lowercase()))
+            })
     }
 
     fn select_any_archive<'a>(&self, assets: &'a [Asset]) -> Option<&'a Asset> {
@@ -265,127 +1041,190 @@ impl AssetSelector {
         }
     }
 
+    /// Archives and single compressed files; `.gz` and friends also cover `.tar.gz`
     fn is_archive(&self, filename: &str) -> bool {
-        filename.ends_with(".tar.gz")
-            || filename.ends_with(".tgz")
-            || filename.ends_with(".tar.xz")
-            || filename.ends_with(".zip")
-    }
-}
-
-pub struct AssetInstaller<'a> {
-    client: &'a Client,
-}
-
-impl<'a> AssetInstaller<'a> {
-    pub fn new(client: &'a Client) -> Self {
-        Self { client }
-    }
-
-    fn install(&self, asset: &Asset, binary_names: &[String], bin_location: &str) -> Result<()> {
-        info!("Downloading asset");
-        let archive_data = self.download_asset(asset)?;
-
-        info!("Extracting binaries: {}", binary_names.join(", "));
-        self.extract_binaries(&archive_data, binary_names, bin_location)?;
-
-        Ok(())
+        const EXTENSIONS: &[&str] = &[
+            ".tar", ".tgz", ".txz", ".tbz", ".tbz2", ".tzst", ".zip", ".gz", ".xz", ".bz2", ".zst",
+        ];
+        EXTENSIONS.iter().any(|ext| filename.ends_with(ext))
     }
 
-    fn download_asset(&self, asset: &Asset) -> Result<Vec<u8>> {
-        let response = self
-            .client
-            .get(&asset.browser_download_url)
-            .header("User-Agent", "picolayer")
-            .send()
-            .context("Failed to download asset")?;
-
-        if !response.status().is_success() {
-            anyhow::bail!("Failed to download asset: {}", response.status());
-        }
-
-        Ok(response.bytes()?.to_vec())
+    /// Assets without an archive extension that are not checksums, signatures or packages
+    fn is_bare_binary(&self, filename: &str) -> bool {
+        const NOT_BINARIES: &[&str] = &[
+            ".sha256",
+            ".sha256sum",
+            ".sha512",
+            ".sha512sum",
+            ".md5",
+            ".txt",
+            ".asc",
+            ".sig",
+            ".pem",
+            ".crt",
+            ".cert",
+            ".sbom",
+            ".json",
+            ".jsonl",
+            ".spdx",
+            ".deb",
+            ".rpm",
+            ".apk",
+            ".msi",
+            ".exe",
+            ".dmg",
+            ".pkg",
+            ".sh",
+            ".whl",
+            ".jar",
+        ];
+        !self.is_archive(filename) && !NOT_BINARIES.iter().any(|ext| filename.ends_with(ext))
     }
+}
 
-    fn extract_binaries(
-        &self,
-        archive_data: &[u8],
-        binary_names: &[String],
-        bin_location: &str,
-    ) -> Result<()> {
-        let temp_dir = tempfile::tempdir()?;
+/// Compression or container of a downloaded asset, detected from its leading bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+    Zip,
+    None,
+}
 
-        if self.is_tar_xz_archive(archive_data) {
-            self.extract_tar_xz(archive_data, binary_names, bin_location, &temp_dir)
+impl Compression {
+    fn detect(data: &[u8]) -> Self {
+        if data.starts_with(&[0x1F, 0x8B]) {
+            Self::Gzip
+        } else if data.starts_with(b"\xFD7zXZ\x00") {
+            Self::Xz
+        } else if data.starts_with(b"BZh") {
+            Self::Bzip2
+        } else if data.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Self::Zstd
+        } else if data.starts_with(b"PK\x03\x04") {
+            Self::Zip
         } else {
-            self.extract_tar_gz(archive_data, binary_names, bin_location, &temp_dir)
+            Self::None
         }
     }
 
-    pub fn is_tar_xz_archive(&self, data: &[u8]) -> bool {
-        // XZ files start with 0xFD, '7', 'z', 'X', 'Z', 0x00
-        data.len() >= 6 && data[0] == 0xFD && &data[1..6] == b"7zXZ\x00"
+    /// Decompress a single stream; zip archives and uncompressed data are read as is
+    fn decoder<'r>(self, reader: impl Read + 'r) -> Result<Box<dyn Read + 'r>> {
+        Ok(match self {
+            Self::Gzip => Box::new(GzDecoder::new(reader)),
+            Self::Xz => Box::new(XzDecoder::new(reader)),
+            Self::Bzip2 => Box::new(BzDecoder::new(reader)),
+            Self::Zstd => Box::new(
+                zstd::stream::read::Decoder::new(reader)
+                    .context("Failed to decompress Zstd asset")?,
+            ),
+            Self::Zip | Self::None => Box::new(reader),
+        })
     }
+}
 
-    fn extract_tar_gz(
-        &self,
-        archive_data: &[u8],
-        binary_names: &[String],
-        bin_location: &str,
-        temp_dir: &tempfile::TempDir,
-    ) -> Result<()> {
-        let archive_path = temp_dir.path().join("download.tar.gz");
-
-        let mut file = File::create(&archive_path)?;
-        file.write_all(archive_data)?;
+/// Tar archives carry `ustar` at offset 257 of the first header
+fn is_tar(data: &[u8]) -> bool {
+    data.get(257..262) == Some(b"ustar")
+}
 
-        let file = File::open(&archive_path)?;
-        let reader = BufReader::new(file);
-        let decoder = GzDecoder::new(reader);
-        let mut archive = Archive::new(decoder);
+/// ELF and Mach-O executables and scripts with a shebang
+fn is_executable(data: &[u8]) -> bool {
+    const MAGIC: &[&[u8]] = &[
+        b"\x7FELF",
+        &[0xFE, 0xED, 0xFA, 0xCE],
+        &[0xFE, 0xED, 0xFA, 0xCF],
+        &[0xCE, 0xFA, 0xED, 0xFE],
+        &[0xCF, 0xFA, 0xED, 0xFE],
+        &[0xCA, 0xFE, 0xBA, 0xBE],
+        b"#!",
+    ];
+    MAGIC.iter().any(|magic| data.starts_with(magic))
+}
 
-        fs::create_dir_all(bin_location).context("Failed to create bin directory")?;
+/// Installs binaries from a downloaded asset
+#[derive(Default)]
+pub struct AssetInstaller<'a> {
+    temp_root: Option<&'a Path>,
+}
 
-        for entry in archive.entries()? {
-            let mut entry = entry?;
-            let path = entry.path()?;
-            let file_name = path
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("")
-                .to_string();
-
-            if binary_names.iter().any(|name| name == &file_name) {
-                self.install_binary(&mut entry, &file_name, bin_location)?;
-            }
-        }
+impl<'a> AssetInstaller<'a> {
+    pub fn new() -> Self {
+        Self { temp_root: None }
+    }
 
-        Ok(())
+    /// Extract archives below the given directory instead of the system temp directory
+    pub fn in_temp_dir(mut self, temp_root: &'a Path) -> Self {
+        self.temp_root = Some(temp_root);
+        self
     }
 
-    pub fn extract_tar_xz(
+    pub(crate) fn extract_binaries(
         &self,
-        archive_data: &[u8],
+        asset_path: &Path,
         binary_names: &[String],
         bin_location: &str,
-        temp_dir: &tempfile::TempDir,
-    ) -> Result<()> {
+    ) -> Result<Vec<PathBuf>> {
+        let temp_dir = match self.temp_root {
+            Some(root) => tempfile::tempdir_in(root)?,
+            None => tempfile::tempdir()?,
+        };
         let extract_dir = temp_dir.path().join("extracted");
-
         fs::create_dir_all(&extract_dir)?;
         fs::create_dir_all(bin_location).context("Failed to create bin directory")?;
 
-        let cursor = std::io::Cursor::new(archive_data);
-        let xz_decoder = XzDecoder::new(cursor);
-        let mut archive = Archive::new(xz_decoder);
+        let mut file = File::open(asset_path)
+            .with_context(|| format!("Failed to open {}", asset_path.display()))?;
+        let mut magic = Vec::new();
+        file.by_ref().take(8).read_to_end(&mut magic)?;
+        file.rewind()?;
+
+        let compression = Compression::detect(&magic);
+        if compression == Compression::Zip {
+            zip::ZipArchive::new(file)
+                .and_then(|mut archive| archive.extract(&extract_dir))
+                .context("Failed to extract zip archive")?;
+            return self.find_and_install_binaries(&extract_dir, binary_names, bin_location);
+        }
 
-        archive
-            .unpack(&extract_dir)
-            .context("Failed to extract tar.xz archive")?;
+        // Peek at the start of the decompressed stream to tell a tarball from a binary
+        let mut decoder = compression.decoder(BufReader::new(file))?;
+        let mut header = Vec::new();
+        decoder
+            .by_ref()
+            .take(512)
+            .read_to_end(&mut header)
+            .with_context(|| format!("Failed to decompress {:?} asset", compression))?;
+        let mut data = Cursor::new(&header).chain(decoder);
+
+        if is_tar(&header) {
+            Archive::new(data)
+                .unpack(&extract_dir)
+                .context("Failed to extract tar archive")?;
+            return self.find_and_install_binaries(&extract_dir, binary_names, bin_location);
+        }
 
-        self.find_and_install_binaries(&extract_dir, binary_names, bin_location)?;
+        // A single binary, compressed or not, is installed under the one requested name
+        let [binary_name] = binary_names else {
+            anyhow::bail!(
+                "Asset is a single file, but {} binary names were given",
+                binary_names.len()
+            );
+        };
+        anyhow::ensure!(
+            is_executable(&header),
+            "Asset is neither a supported archive nor an executable"
+        );
+        let mut binary = File::create(extract_dir.join(binary_name))?;
+        io::copy(&mut data, &mut binary)
+            .with_context(|| format!("Failed to decompress {:?} asset", compression))?;
+        self.find_and_install_binaries(&extract_dir, binary_names, bin_location)
+    }
 
-        Ok(())
+    pub fn is_tar_xz_archive(&self, data: &[u8]) -> bool {
+        Compression::detect(data) == Compression::Xz
     }
 
     fn find_and_install_binaries(
@@ -393,7 +1232,8 @@ impl<'a> AssetInstaller<'a> {
         extract_dir: &std::path::Path,
         binary_names: &[String],
         bin_location: &str,
-    ) -> Result<()> {
+    ) -> Result<Vec<PathBuf>> {
+        let mut installed = Vec::new();
         for entry in walkdir::WalkDir::new(extract_dir) {
             let entry = entry?;
             if entry.file_type().is_file() {
@@ -416,57 +1256,49 @@ impl<'a> AssetInstaller<'a> {
                     }
 
                     info!("Installed: {} -> {}", file_name, dest_path.display());
+                    installed.push(dest_path);
                 }
             }
         }
 
-        Ok(())
-    }
-
-    fn install_binary(
-        &self,
-        entry: &mut tar::Entry<impl std::io::Read>,
-        file_name: &str,
-        bin_location: &str,
-    ) -> Result<()> {
-        let dest_path = Path::new(bin_location).join(file_name);
-        entry.unpack(&dest_path)?;
-
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&dest_path)?.permissions();
-            perms.set_mode(0o755);
-            fs::set_permissions(&dest_path, perms)?;
-        }
-
-        info!("Installed: {} -> {}", file_name, dest_path.display());
-        Ok(())
+        Ok(installed)
     }
 }
 
-struct AssetVerifier<'a> {
+pub(crate) struct AssetVerifier<'a> {
     client: &'a Client,
+    token: Option<&'a Token>,
 }
 
 impl<'a> AssetVerifier<'a> {
-    fn new(client: &'a Client) -> Self {
-        Self { client }
+    pub(crate) fn new(client: &'a Client, token: Option<&'a Token>) -> Self {
+        Self { client, token }
     }
 
-    fn verify(&self, assets: &[Asset], asset: &Asset, gpg_key: Option<&str>) -> Result<()> {
+    /// Verify the asset, returning the verified hash unless a signature was checked
+    fn verify(
+        &self,
+        asset: &Asset,
+        download: &Download,
+        checksum_asset: &Asset,
+        gpg_key: Option<&str>,
+    ) -> Result<Option<String>> {
         info!("Verifying asset");
 
-        let checksum_asset = self.find_checksum_asset(assets, asset)?;
-
-        if checksum_asset.name.ends_with(".asc") || checksum_asset.name.ends_with(".sig") {
-            return self.verify_gpg_signature(asset, checksum_asset, gpg_key);
+        if is_signature(&checksum_asset.name) {
+            self.verify_gpg_signature(&download.path, checksum_asset, gpg_key)?;
+            return Ok(None);
         }
 
-        self.verify_sha256_checksum(asset, checksum_asset, assets)
+        self.verify_sha256_checksum(asset, download, checksum_asset)
+            .map(Some)
     }
 
-    fn verify_with_checksum_text(&self, asset: &Asset, checksum_text: &str) -> Result<()> {
+    pub(crate) fn verify_with_checksum_text(
+        &self,
+        download: &Download,
+        checksum_text: &str,
+    ) -> Result<String> {
         info!("Verifying asset with provided checksum text");
 
         // Expects checksum text format: "algorithm:hash"
@@ -484,13 +1316,10 @@ impl<'a> AssetVerifier<'a> {
             anyhow::bail!("Only sha256 algorithm is currently supported");
         }
 
-        info!("Downloading asset for verification");
-        let asset_data = self.download_asset(asset)?;
-        let computed_hash = compute_sha256(&asset_data);
-
+        let computed_hash = &download.sha256;
         if computed_hash.to_lowercase() == expected_hash.to_lowercase() {
             info!("Checksum verification passed");
-            Ok(())
+            Ok(format!("sha256:{}", computed_hash))
         } else {
             anyhow::bail!(
                 "Checksum verification failed!\nExpected: {}\nComputed: {}",
@@ -548,13 +1377,15 @@ impl<'a> AssetVerifier<'a> {
 
     fn verify_gpg_signature(
         &self,
-        asset: &Asset,
+        asset_path: &Path,
         signature_asset: &Asset,
         gpg_key: Option<&str>,
     ) -> Result<()> {
         if let Some(key_content) = gpg_key {
             info!("Verifying GPG signature");
-            GpgVerifier::new(self.client).verify(asset, signature_asset, key_content)
+            GpgVerifier::new(self.client)
+                .github_token(self.token)
+                .verify(asset_path, signature_asset, key_content)
         } else {
             warn!("Found signature file but no GPG key provided");
             info!("Use --gpg-key option to enable GPG verification");
@@ -565,21 +1396,20 @@ impl<'a> AssetVerifier<'a> {
     fn verify_sha256_checksum(
         &self,
         asset: &Asset,
+        download: &Download,
         checksum_asset: &Asset,
-        _assets: &[Asset],
-    ) -> Result<()> {
+    ) -> Result<String> {
         info!("Verifying SHA256 checksum");
         info!("Checksum file: {}", checksum_asset.name);
 
-        let asset_data = self.download_asset(asset)?;
-        let computed_hash = compute_sha256(&asset_data);
+        let computed_hash = &download.sha256;
 
         let checksum_content = self.download_checksum(checksum_asset)?;
         let expected_hash = self.parse_checksum(&checksum_content, &asset.name)?;
 
         if computed_hash.to_lowercase() == expected_hash.to_lowercase() {
             info!("Checksum verification passed");
-            Ok(())
+            Ok(format!("sha256:{}", computed_hash))
         } else {
             anyhow::bail!(
                 "Checksum verification failed!\nExpected: {}\nComputed: {}",
@@ -589,26 +1419,10 @@ impl<'a> AssetVerifier<'a> {
         }
     }
 
-    fn download_asset(&self, asset: &Asset) -> Result<Vec<u8>> {
-        let response = self
-            .client
-            .get(&asset.browser_download_url)
-            .header("User-Agent", "picolayer")
-            .send()
-            .context("Failed to download asset")?;
-
-        if !response.status().is_success() {
-            anyhow::bail!("Failed to download asset: {}", response.status());
-        }
-
-        Ok(response.bytes()?.to_vec())
-    }
-
     fn download_checksum(&self, checksum_asset: &Asset) -> Result<String> {
-        let response = self
-            .client
-            .get(&checksum_asset.browser_download_url)
-            .header("User-Agent", "picolayer")
+        let url = &checksum_asset.browser_download_url;
+        let request = self.client.get(url).header("User-Agent", "picolayer");
+        let response = github::authorize(request, url, self.token)
             .send()
             .context("Failed to download checksum")?;
 
@@ -638,24 +1452,44 @@ impl<'a> AssetVerifier<'a> {
     }
 }
 
-struct GpgVerifier<'a> {
+pub(crate) struct GpgVerifier<'a> {
     client: &'a Client,
+    token: Option<&'a Token>,
 }
 
 impl<'a> GpgVerifier<'a> {
-    fn new(client: &'a Client) -> Self {
-        Self { client }
+    pub(crate) fn new(client: &'a Client) -> Self {
+        Self {
+            client,
+            token: None,
+        }
     }
 
-    fn verify(&self, asset: &Asset, signature_asset: &Asset, gpg_key_content: &str) -> Result<()> {
-        use pgp::composed::{Deserializable, DetachedSignature};
-        use std::io::Cursor;
-
-        info!("Downloading asset for verification");
-        let asset_data = self.download_data(&asset.browser_download_url)?;
+    /// Send the token when downloading signatures from GitHub
+    fn github_token(mut self, token: Option<&'a Token>) -> Self {
+        self.token = token;
+        self
+    }
 
+    fn verify(
+        &self,
+        asset_path: &Path,
+        signature_asset: &Asset,
+        gpg_key_content: &str,
+    ) -> Result<()> {
         info!("Downloading signature file");
         let sig_data = self.download_data(&signature_asset.browser_download_url)?;
+        self.verify_signature(asset_path, sig_data, gpg_key_content)
+    }
+
+    /// Check a detached signature, armored or binary, of the file at `asset_path`
+    pub(crate) fn verify_signature(
+        &self,
+        asset_path: &Path,
+        sig_data: Vec<u8>,
+        gpg_key_content: &str,
+    ) -> Result<()> {
+        use pgp::composed::{Deserializable, DetachedSignature};
 
         info!("Loading GPG public key");
         let public_key = self.load_public_key(gpg_key_content)?;
@@ -675,8 +1509,10 @@ impl<'a> GpgVerifier<'a> {
         };
 
         info!("Verifying signature");
+        let asset = BufReader::new(File::open(asset_path)?);
         signature
-            .verify(&public_key, &asset_data[..])
+            .signature
+            .verify(&public_key, asset)
             .context("GPG signature verification failed")?;
 
         info!("GPG signature verification passed!");
@@ -684,10 +1520,8 @@ impl<'a> GpgVerifier<'a> {
     }
 
     fn download_data(&self, url: &str) -> Result<Vec<u8>> {
-        let response = self
-            .client
-            .get(url)
-            .header("User-Agent", "picolayer")
+        let request = self.client.get(url).header("User-Agent", "picolayer");
+        let response = github::authorize(request, url, self.token)
             .send()
             .context("Failed to download")?;
 
@@ -698,8 +1532,13 @@ impl<'a> GpgVerifier<'a> {
         Ok(response.bytes()?.to_vec())
     }
 
-    fn load_public_key(&self, key_content: &str) -> Result<pgp::composed::SignedPublicKey> {
+    /// Load a public key from a URL, file path or key content, armored or binary
+    pub(crate) fn load_public_key(
+        &self,
+        key_content: &str,
+    ) -> Result<pgp::composed::SignedPublicKey> {
         use pgp::composed::{Deserializable, SignedPublicKey};
+        use std::io::Cursor;
 
         let key_data = if key_content.starts_with("http://") || key_content.starts_with("https://")
         {
@@ -716,16 +1555,27 @@ impl<'a> GpgVerifier<'a> {
             }
 
             response
-                .text()
+                .bytes()
                 .context("Failed to read GPG public key response")?
+                .to_vec()
         } else if std::path::Path::new(key_content).exists() {
-            std::fs::read_to_string(key_content).context("Failed to read GPG key file")?
+            std::fs::read(key_content).context("Failed to read GPG key file")?
         } else {
-            key_content.to_string()
+            key_content.as_bytes().to_vec()
         };
 
-        let (public_key, _headers) =
-            SignedPublicKey::from_string(&key_data).context("Failed to parse GPG public key")?;
+        let public_key = if key_data.trim_ascii_start().starts_with(b"-----BEGIN") {
+            // ASCII-armored key
+            let key_str =
+                String::from_utf8(key_data).context("Failed to convert GPG key to string")?;
+            SignedPublicKey::from_string(&key_str)
+                .context("Failed to parse GPG public key")?
+                .0
+        } else {
+            // Binary key
+            SignedPublicKey::from_bytes(Cursor::new(&key_data[..]))
+                .context("Failed to parse GPG public key")?
+        };
 
         Ok(public_key)
     }
@@ -793,12 +1643,6 @@ fn parse_checksum_line(content: &str, asset_name: &str) -> Result<String> {
     anyhow::bail!("Checksum not found for asset: {}", asset_name)
 }
 
-fn compute_sha256(data: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    hex::encode(hasher.finalize())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -806,12 +1650,32 @@ mod tests {
 
     #[test]
     #[serial]
-    fn test_compute_sha256() {
-        let data = b"hello world";
-        let hash = compute_sha256(data);
-        assert_eq!(
-            hash,
-            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+    fn test_verify_with_checksum_text() {
+        let client = Client::new();
+        let verifier = AssetVerifier::new(&client, None);
+        let download = Download {
+            path: PathBuf::from("asset"),
+            sha256: "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9".to_string(),
+            size: 11,
+        };
+
+        let verified = verifier
+            .verify_with_checksum_text(
+                &download,
+                "SHA256:B94D27B9934D3E08A52E52D7DA7DABFAC484EFE37A5380EE9088F7ACE2EFCDE9",
+            )
+            .unwrap();
+        assert_eq!(verified, format!("sha256:{}", download.sha256));
+
+        assert!(
+            verifier
+                .verify_with_checksum_text(&download, &format!("sha256:{}", "0".repeat(64)))
+                .is_err()
+        );
+        assert!(
+            verifier
+                .verify_with_checksum_text(&download, "md5:abc")
+                .is_err()
         );
     }
 
@@ -866,14 +1730,143 @@ mod tests {
         assert!(selector.is_archive("file.tgz"));
         assert!(selector.is_archive("file.tar.xz"));
         assert!(selector.is_archive("file.zip"));
+        assert!(selector.is_archive("file.tar.bz2"));
+        assert!(selector.is_archive("file.tar.zst"));
+        assert!(selector.is_archive("file.gz"));
         assert!(!selector.is_archive("file.txt"));
+
+        assert!(selector.is_bare_binary("jq-linux-amd64"));
+        assert!(selector.is_bare_binary("kubectl-1.30.0-linux-amd64"));
+        assert!(!selector.is_bare_binary("jq-linux-amd64.sha256"));
+        assert!(!selector.is_bare_binary("tool-linux-amd64.deb"));
+        assert!(!selector.is_bare_binary("file.tar.gz"));
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn test_asset_selector_bare_binary_fallback() {
+        let asset = |name: &str| Asset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+            size: 0,
+        };
+        let selector = AssetSelector::new();
+
+        let assets = vec![
+            asset("jq-linux-amd64.sha256"),
+            asset("jq-linux-amd64"),
+            asset("jq-macos-arm64"),
+        ];
+        assert_eq!(
+            selector.select(&assets, None).unwrap().name,
+            "jq-linux-amd64"
+        );
+
+        let assets = vec![asset("tool-linux-amd64"), asset("tool-linux-amd64.tar.gz")];
+        assert_eq!(
+            selector.select(&assets, None).unwrap().name,
+            "tool-linux-amd64.tar.gz"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_extract_binaries_formats() {
+        use std::io::Write;
+
+        let script = b"#!/bin/sh\necho picolayer\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(script.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        let mut builder = tar::Builder::new(Vec::new());
+        builder
+            .append_data(&mut header, "tool-1.0/bin/tool", &script[..])
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        let gzip = |data: &[u8]| {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        };
+        let xz = |data: &[u8]| {
+            let mut encoder = xz::write::XzEncoder::new(Vec::new(), 6);
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        };
+        let bzip2 = |data: &[u8]| {
+            let mut encoder =
+                bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        };
+        let zip = {
+            let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            writer
+                .start_file("tool-1.0/tool", zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(script).unwrap();
+            writer.finish().unwrap().into_inner()
+        };
+
+        let assets = [
+            ("tar.gz", gzip(&tar)),
+            ("tar.xz", xz(&tar)),
+            ("tar.bz2", bzip2(&tar)),
+            ("tar.zst", zstd::encode_all(&tar[..], 0).unwrap()),
+            ("tar", tar.clone()),
+            ("zip", zip),
+            ("gz", gzip(script)),
+            ("xz", xz(script)),
+            ("bz2", bzip2(script)),
+            ("zst", zstd::encode_all(&script[..], 0).unwrap()),
+            ("bare", script.to_vec()),
+        ];
+
+        let installer = AssetInstaller::new();
+        let downloads = tempfile::tempdir().unwrap();
+        let write_asset = |name: &str, data: &[u8]| {
+            let path = downloads.path().join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+        let binary_names = vec!["tool".to_string()];
+        for (format, data) in assets {
+            let bin = tempfile::tempdir().unwrap();
+            let bin_location = bin.path().to_str().unwrap();
+            let installed = installer
+                .extract_binaries(&write_asset(format, &data), &binary_names, bin_location)
+                .unwrap_or_else(|e| panic!("{}: {:#}", format, e));
+            assert_eq!(installed, vec![bin.path().join("tool")], "{}", format);
+            assert_eq!(fs::read(&installed[0]).unwrap(), script, "{}", format);
+        }
+
+        let bin = tempfile::tempdir().unwrap();
+        let bin_location = bin.path().to_str().unwrap();
+        assert!(
+            installer
+                .extract_binaries(
+                    &write_asset("text", b"not a binary"),
+                    &binary_names,
+                    bin_location
+                )
+                .is_err()
+        );
+        let two_names = vec!["tool".to_string(), "other".to_string()];
+        assert!(
+            installer
+                .extract_binaries(&write_asset("script", script), &two_names, bin_location)
+                .is_err()
+        );
     }
 
     #[test]
     #[serial]
     fn test_asset_installer_is_tar_xz_archive() {
-        let client = Client::new();
-        let installer = AssetInstaller::new(&client);
+        let installer = AssetInstaller::new();
 
         // XZ magic bytes: 0xFD, '7', 'z', 'X', 'Z', 0x00
         let xz_data = vec![0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00];
@@ -882,4 +1875,416 @@ mod tests {
         let not_xz_data = vec![0x00, 0x01, 0x02, 0x03, 0x04, 0x05];
         assert!(!installer.is_tar_xz_archive(&not_xz_data));
     }
+
+    const TEST_PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatKtixYJKwYBBAHaRw8BAQdATBCOMRnbn5K99Vst18eqKe8H573mI2mbcfK8
+y6H2dMm0IXBpY29sYXllciB0ZXN0IDx0ZXN0QGV4YW1wbGUuY29tPoiQBBMWCAA4
+FiEEtIHFwMDb1o+uUCpCcrAHo4dmBq4FAmrSrYsCGwMFCwkIBwIGFQoJCAsCBBYC
+AwECHgECF4AACgkQcrAHo4dmBq5bsQEAnA5d13AqRw13iyr0/AlR1kfXsqgUrwD9
+iVtVrFcV2TQBAKyFA14LjFTfMd3Ok67ltZxEkEjR4P1sCtfrZ1Vx3OsM
+=P4aG
+-----END PGP PUBLIC KEY BLOCK-----
+";
+
+    #[test]
+    #[serial]
+    fn test_release_plan_display() {
+        let plan = ReleasePlan {
+            repo: "cli/cli".to_string(),
+            tag: "v2.50.0".to_string(),
+            checksum: ChecksumSource::File("gh_2.50.0_checksums.txt".to_string()),
+            binaries: vec![PathBuf::from("/usr/local/bin/gh")],
+            asset: Asset {
+                name: "gh_2.50.0_linux_amd64.tar.gz".to_string(),
+                browser_download_url: "https://example.com/gh.tar.gz".to_string(),
+                size: 2048,
+            },
+            checksum_asset: None,
+        };
+        assert_eq!(
+            plan.to_string(),
+            "gh-release would install cli/cli v2.50.0:\n  \
+             asset: gh_2.50.0_linux_amd64.tar.gz (2.0 KiB)\n  \
+             url: https://example.com/gh.tar.gz\n  \
+             checksum: sha256 from gh_2.50.0_checksums.txt\n  \
+             binary: /usr/local/bin/gh\n"
+        );
+        assert_eq!(
+            ChecksumSource::UnverifiedSignature("gh.tar.gz.asc".to_string()).to_string(),
+            "GPG signature gh.tar.gz.asc (skipped, no GPG key)"
+        );
+
+        let json = serde_json::to_value(&plan).unwrap();
+        assert_eq!(json["asset"]["name"], "gh_2.50.0_linux_amd64.tar.gz");
+        assert_eq!(json["checksum"]["type"], "file");
+        assert_eq!(json["checksum"]["name"], "gh_2.50.0_checksums.txt");
+        assert!(json.get("checksum_asset").is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_version_spec_parse() {
+        assert_eq!(VersionSpec::parse("latest").unwrap(), VersionSpec::Latest);
+        assert_eq!(
+            VersionSpec::parse("v2.40.0").unwrap(),
+            VersionSpec::Tag("v2.40.0")
+        );
+        assert_eq!(VersionSpec::parse("1.2").unwrap(), VersionSpec::Tag("1.2"));
+        for range in ["^1.2", "~2.40", "<3", ">=1.2, <2", "1.x", "*"] {
+            assert_eq!(
+                VersionSpec::parse(range).unwrap(),
+                VersionSpec::Range(VersionReq::parse(range).unwrap())
+            );
+        }
+        assert!(VersionSpec::parse("^not.a.version").is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_tag_version() {
+        let version = |v: &str| Some(Version::parse(v).unwrap());
+        assert_eq!(tag_version("v2.40.1", None), version("2.40.1"));
+        assert_eq!(tag_version("1.2", None), version("1.2.0"));
+        assert_eq!(tag_version("v3-rc.1", None), version("3.0.0-rc.1"));
+        assert_eq!(tag_version("release-1.2.3", None), None);
+
+        let regex = Regex::new(r"^(?:release-|cli-v)(?P<version>.+)$").unwrap();
+        assert_eq!(tag_version("release-1.2.3", Some(&regex)), version("1.2.3"));
+        assert_eq!(tag_version("cli-v1.2.3", Some(&regex)), version("1.2.3"));
+        assert_eq!(tag_version("lib-v1.2.3", Some(&regex)), None);
+    }
+
+    #[test]
+    #[serial]
+    fn test_select_release() {
+        let release = |tag: &str, prerelease: bool, draft: bool| Release {
+            tag_name: tag.to_string(),
+            assets: Vec::new(),
+            prerelease,
+            draft,
+        };
+        let releases = || {
+            vec![
+                release("v3.0.0-rc.1", true, false),
+                release("v3.1.0", false, true),
+                release("v2.41.0", false, false),
+                release("v2.40.2", false, false),
+                release("v1.9.0", false, false),
+                release("nightly", true, false),
+            ]
+        };
+        let select = |req: &str, include_prereleases: bool| {
+            select_release(
+                releases(),
+                &VersionReq::parse(req).unwrap(),
+                None,
+                include_prereleases,
+            )
+            .map(|release| release.tag_name)
+        };
+
+        assert_eq!(select("*", false).as_deref(), Some("v2.41.0"));
+        assert_eq!(select("~2.40", false).as_deref(), Some("v2.40.2"));
+        assert_eq!(select("<2", false).as_deref(), Some("v1.9.0"));
+        assert_eq!(select("^3", false), None);
+        assert_eq!(select("^3", true).as_deref(), Some("v3.1.0"));
+        assert_eq!(select("<3.1", true).as_deref(), Some("v3.0.0-rc.1"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_api_base() {
+        let base = |forge, api_url| api_base(forge, api_url).unwrap().to_string();
+        assert_eq!(base(Forge::Github, None), "https://api.github.com/");
+        assert_eq!(
+            base(Forge::Github, Some("https://ghe.example.com")),
+            "https://ghe.example.com/api/v3"
+        );
+        assert_eq!(
+            base(Forge::Github, Some("https://ghe.example.com/api/v3")),
+            "https://ghe.example.com/api/v3"
+        );
+        assert_eq!(
+            base(Forge::Gitea, Some("https://codeberg.org/")),
+            "https://codeberg.org/api/v1"
+        );
+        assert_eq!(base(Forge::Gitlab, None), "https://gitlab.com/api/v4");
+        assert!(api_base(Forge::Gitea, None).is_err());
+        assert!(api_base(Forge::Github, Some("not a url")).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_verification() {
+        let installed = |checksum: Option<&str>, signature: Option<&str>| ReleaseInstall {
+            repo: "cli/cli".to_string(),
+            tag: "v2.50.0".to_string(),
+            asset: None,
+            checksum: checksum.map(String::from),
+            signature: signature.map(String::from),
+            binaries: Vec::new(),
+            skipped: None,
+        };
+        assert_eq!(verification(&installed(None, None), false), "none");
+        // A GPG key without a signature asset verifies nothing
+        assert_eq!(verification(&installed(None, None), true), "none");
+        assert_eq!(
+            verification(&installed(Some("sha256:abc"), None), false),
+            "checksum"
+        );
+        assert_eq!(
+            verification(&installed(Some("sha256:abc"), None), true),
+            "checksum-text"
+        );
+        assert_eq!(
+            verification(&installed(None, Some("gh.tar.gz.sig")), false),
+            "gpg"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_github_token_not_sent_to_other_forges() {
+        use reqwest::header::AUTHORIZATION;
+
+        let binary_names = vec!["tool".to_string()];
+        let config = |source, api_url| GhReleaseConfig {
+            repo: "group/tool",
+            binary_names: &binary_names,
+            version: "latest",
+            install_dir: "/usr/local/bin",
+            filter: None,
+            verify_checksum: false,
+            checksum_text: None,
+            gpg_key: None,
+            tag_regex: None,
+            include_prereleases: false,
+            token_file: None,
+            source,
+            api_url,
+            force: false,
+        };
+        let client = Client::new();
+        let authorized = |token: Option<&Token>, url: &str| {
+            github::authorize(client.get(url), url, token)
+                .build()
+                .unwrap()
+                .headers()
+                .contains_key(AUTHORIZATION)
+        };
+
+        unsafe {
+            std::env::set_var("GITHUB_TOKEN", "ghp_secret");
+        }
+        let github_token = resolve_token(&config(Forge::Github, None)).unwrap();
+        let enterprise_token =
+            resolve_token(&config(Forge::Github, Some("https://any.example.com"))).unwrap();
+        let gitea_token =
+            resolve_token(&config(Forge::Gitea, Some("https://codeberg.org"))).unwrap();
+        let gitlab_token = resolve_token(&config(Forge::Gitlab, None)).unwrap();
+        unsafe {
+            std::env::remove_var("GITHUB_TOKEN");
+        }
+
+        assert!(authorized(
+            github_token.as_ref(),
+            "https://api.github.com/repos/cli/cli/releases/latest"
+        ));
+        assert!(!authorized(
+            github_token.as_ref(),
+            "https://codeberg.org/api/v1/repos/group/tool/releases/latest"
+        ));
+        assert!(enterprise_token.is_none());
+        assert!(gitea_token.is_none());
+        assert!(gitlab_token.is_none());
+
+        unsafe {
+            std::env::set_var("GH_ENTERPRISE_TOKEN", "ghe_secret");
+        }
+        let enterprise_token =
+            resolve_token(&config(Forge::Github, Some("https://ghe.example.com"))).unwrap();
+        unsafe {
+            std::env::remove_var("GH_ENTERPRISE_TOKEN");
+        }
+        assert!(authorized(
+            enterprise_token.as_ref(),
+            "https://ghe.example.com/api/v3/repos/cli/cli/releases/latest"
+        ));
+        assert!(!authorized(
+            enterprise_token.as_ref(),
+            "http://ghe.example.com/api/v3/repos/cli/cli/releases/latest"
+        ));
+        assert!(!authorized(
+            enterprise_token.as_ref(),
+            "https://api.github.com/repos/cli/cli/releases/latest"
+        ));
+
+        unsafe {
+            std::env::set_var("GITLAB_TOKEN", "glpat_secret");
+        }
+        let gitlab_token = resolve_token(&config(Forge::Gitlab, None)).unwrap();
+        unsafe {
+            std::env::remove_var("GITLAB_TOKEN");
+        }
+        assert!(authorized(
+            gitlab_token.as_ref(),
+            "https://gitlab.com/api/v4/projects/group%2Ftool/releases"
+        ));
+        assert!(!authorized(
+            gitlab_token.as_ref(),
+            "https://api.github.com/repos/cli/cli/releases/latest"
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_release_source_urls() {
+        let client = Client::new();
+        let api = |forge, api_url| Api {
+            client: &client,
+            token: None,
+            base: api_base(forge, api_url).unwrap(),
+        };
+
+        let github = GitHubSource::github(api(Forge::Github, None));
+        assert_eq!(
+            github
+                .releases_url("cli/cli", &["tags", "v2.40.0"])
+                .as_str(),
+            "https://api.github.com/repos/cli/cli/releases/tags/v2.40.0"
+        );
+        let gitea = GitHubSource::gitea(api(Forge::Gitea, Some("https://codeberg.org")));
+        assert_eq!(
+            gitea.releases_url("forgejo/forgejo", &["latest"]).as_str(),
+            "https://codeberg.org/api/v1/repos/forgejo/forgejo/releases/latest"
+        );
+
+        // GitLab project paths and tags are single, encoded path segments
+        let gitlab = api(Forge::Gitlab, None);
+        assert_eq!(
+            gitlab
+                .url(["projects", "group/sub/tool", "releases", "tools/v1.0"])
+                .as_str(),
+            "https://gitlab.com/api/v4/projects/group%2Fsub%2Ftool/releases/tools%2Fv1.0"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_gitlab_release_into_release() {
+        let release: GitLabRelease = serde_json::from_str(
+            r#"{
+                "tag_name": "v1.2.0",
+                "upcoming_release": true,
+                "assets": {
+                    "sources": [{"format": "zip", "url": "https://gitlab.com/archive.zip"}],
+                    "links": [
+                        {
+                            "name": "tool-linux-amd64.tar.gz",
+                            "url": "https://gitlab.com/group/tool/-/package_files/1/download",
+                            "direct_asset_url": "https://gitlab.com/group/tool/-/releases/v1.2.0/downloads/tool-linux-amd64.tar.gz"
+                        },
+                        {"name": "checksums.txt", "url": "https://example.com/checksums.txt"}
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let release = Release::from(release);
+        assert_eq!(release.tag_name, "v1.2.0");
+        assert!(release.prerelease);
+        assert_eq!(release.assets.len(), 2);
+        assert!(
+            release.assets[0]
+                .browser_download_url
+                .ends_with("/downloads/tool-linux-amd64.tar.gz")
+        );
+        assert_eq!(
+            release.assets[1].browser_download_url,
+            "https://example.com/checksums.txt"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_mentions_version() {
+        assert!(mentions_version(
+            "gh version 2.40.0 (2023-12-07)\n",
+            "v2.40.0"
+        ));
+        assert!(mentions_version("ripgrep 14.1.0\n", "14.1.0"));
+        assert!(!mentions_version("gh version 2.40.1\n", "v2.40.0"));
+        assert!(!mentions_version("tool 12.40.0\n", "2.40.0"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_already_installed_from_receipt() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("gh");
+        fs::write(&binary, "binary").unwrap();
+        let binary_names = vec!["gh".to_string()];
+        let install_dir = dir.path().display().to_string();
+        let config = GhReleaseConfig {
+            repo: "cli/cli",
+            binary_names: &binary_names,
+            version: "v2.40.0",
+            install_dir: &install_dir,
+            filter: None,
+            verify_checksum: false,
+            checksum_text: None,
+            gpg_key: None,
+            tag_regex: None,
+            include_prereleases: false,
+            token_file: None,
+            source: Forge::Github,
+            api_url: None,
+            force: false,
+        };
+
+        let mut receipt = Receipt::new("cli/cli", "gh-release", "cli/cli", "v2.40.0");
+        receipt.files.push(ReceiptFile::hashed(&binary).unwrap());
+        receipts::ReceiptStore::open(&dir.path().join("state"))
+            .unwrap()
+            .record(&receipt)
+            .unwrap();
+
+        unsafe {
+            std::env::set_var("PICOLAYER_STATE_DIR", dir.path().join("state"));
+        }
+        let skipped = already_installed(&config, "2.40.0").unwrap();
+        assert_eq!(skipped.tag, "v2.40.0");
+        assert_eq!(skipped.binaries, vec![binary.clone()]);
+        assert!(skipped.skipped.unwrap().contains("--force"));
+        assert!(already_installed(&config, "v2.41.0").is_none());
+
+        fs::write(&binary, "modified").unwrap();
+        assert!(already_installed(&config, "v2.40.0").is_none());
+        unsafe {
+            std::env::remove_var("PICOLAYER_STATE_DIR");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_gpg_verifier_load_public_key() {
+        use pgp::ser::Serialize;
+
+        let client = Client::new();
+        let verifier = GpgVerifier::new(&client);
+
+        let armored = verifier.load_public_key(TEST_PUBLIC_KEY).unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary_path = temp_dir.path().join("key.gpg");
+        fs::write(&binary_path, armored.to_bytes().unwrap()).unwrap();
+        let binary = verifier
+            .load_public_key(binary_path.to_str().unwrap())
+            .unwrap();
+        assert_eq!(binary, armored);
+
+        assert!(verifier.load_public_key("not a key").is_err());
+    }
 }
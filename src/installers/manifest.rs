@@ -0,0 +1,454 @@
+use crate::installers::apk::Apk;
+use crate::installers::apt_get::AptGet;
+use crate::installers::package_manager::{self, PackageInstall, PackageManager, PackagePlan};
+use crate::installers::pkg::{self, PackageMapping};
+use crate::installers::session::Session;
+use crate::installers::{devcontainer_feature, gh_release, x};
+use crate::utils::leftovers::Leftovers;
+use crate::utils::os_detect;
+use crate::utils::output::OutputFormat;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Installs declared in a `picolayer.toml` manifest
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Packages installed with the native package manager of the detected distribution
+    pub pkg: Option<PkgSection>,
+    pub apt_get: Option<AptGetSection>,
+    pub apk: Option<ApkSection>,
+    #[serde(default)]
+    pub devcontainer_feature: Vec<FeatureSection>,
+    #[serde(default)]
+    pub gh_release: Vec<GhReleaseSection>,
+    #[serde(default)]
+    pub x: Vec<RunSection>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PkgSection {
+    pub packages: Vec<String>,
+    #[serde(default)]
+    pub map: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AptGetSection {
+    pub packages: Vec<String>,
+    #[serde(default)]
+    pub ppas: Vec<String>,
+    #[serde(default)]
+    pub force_ppas_on_non_ubuntu: bool,
+    #[serde(default)]
+    pub repos: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApkSection {
+    pub packages: Vec<String>,
+    #[serde(default)]
+    pub repositories: Vec<String>,
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+/// Arguments of `devcontainer_feature::install`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeatureSection {
+    pub feature: String,
+    #[serde(default)]
+    pub options: HashMap<String, toml::Value>,
+    pub remote_user: Option<String>,
+    #[serde(default)]
+    pub envs: HashMap<String, String>,
+}
+
+/// Fields of `GhReleaseConfig`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GhReleaseSection {
+    pub repo: String,
+    pub binary_names: Vec<String>,
+    #[serde(default = "default_version")]
+    pub version: String,
+    #[serde(default = "default_install_dir")]
+    pub install_dir: String,
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub verify_checksum: bool,
+    pub checksum_text: Option<String>,
+    pub gpg_key: Option<String>,
+    pub tag_regex: Option<String>,
+    #[serde(default)]
+    pub include_prereleases: bool,
+    pub token_file: Option<String>,
+    #[serde(default)]
+    pub source: gh_release::Forge,
+    pub api_url: Option<String>,
+}
+
+/// Fields of `RunConfig`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunSection {
+    pub tool: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_working_dir")]
+    pub working_dir: String,
+    #[serde(default)]
+    pub env_vars: Vec<String>,
+}
+
+/// A package manager and the packages it installs
+type PackageStep = (Box<dyn PackageManager>, Vec<String>);
+
+fn default_version() -> String {
+    "latest".to_string()
+}
+
+fn default_install_dir() -> String {
+    "/usr/local/bin".to_string()
+}
+
+fn default_working_dir() -> String {
+    ".".to_string()
+}
+
+/// What applying a manifest would install, section by section
+#[derive(Debug, Default, Serialize)]
+pub struct ManifestPlan {
+    pub packages: Vec<PackagePlan>,
+    pub features: Vec<devcontainer_feature::FeaturePlan>,
+    pub releases: Vec<gh_release::ReleasePlan>,
+    pub runs: Vec<x::RunPlan>,
+}
+
+impl fmt::Display for ManifestPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for plan in &self.packages {
+            write!(f, "{}", plan)?;
+        }
+        for plan in &self.features {
+            write!(f, "{}", plan)?;
+        }
+        for plan in &self.releases {
+            write!(f, "{}", plan)?;
+        }
+        for plan in &self.runs {
+            write!(f, "{}", plan)?;
+        }
+        Ok(())
+    }
+}
+
+/// What applying a manifest installed, section by section
+#[derive(Debug, Default, Serialize)]
+pub struct ManifestInstall {
+    pub packages: Vec<PackageInstall>,
+    pub features: Vec<devcontainer_feature::FeatureInstall>,
+    pub releases: Vec<gh_release::ReleaseInstall>,
+    pub runs: Vec<x::RunResult>,
+}
+
+/// Load a manifest and apply it, sending the stdout of pkgx tools where `output` needs it
+pub fn apply(path: &Path, force: bool, output: OutputFormat) -> Result<ManifestInstall> {
+    let manifest = Manifest::load(path)?;
+    manifest.apply(&Session::new()?, force, output)
+}
+
+/// Load a manifest and report what applying it would install
+pub fn plan(path: &Path) -> Result<ManifestPlan> {
+    Manifest::load(path)?.plan()
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+        contents.parse()
+    }
+
+    /// Install system packages, then features, release binaries and pkgx tools,
+    /// cleaning package manager caches once at the end
+    pub fn apply(
+        &self,
+        session: &Session,
+        force: bool,
+        output: OutputFormat,
+    ) -> Result<ManifestInstall> {
+        let steps = self.package_steps()?;
+        for (manager, _) in &steps {
+            anyhow::ensure!(manager.detect(), manager.unsupported_reason());
+        }
+        let leftovers: Vec<Leftovers> = steps
+            .iter()
+            .map(|(manager, _)| Leftovers::capture(manager.leftovers()))
+            .collect();
+
+        let applied = self.apply_steps(session, &steps, force, output);
+        let cleaned = clean_up(&steps, &leftovers);
+        let installed = applied?;
+        cleaned?;
+        Ok(installed)
+    }
+
+    /// Plan every section without changing the system
+    pub fn plan(&self) -> Result<ManifestPlan> {
+        let mut plan = ManifestPlan::default();
+
+        for (manager, packages) in self.package_steps()? {
+            plan.packages
+                .push(package_manager::plan(manager.as_ref(), &packages)?);
+        }
+
+        for feature in &self.devcontainer_feature {
+            plan.features.push(devcontainer_feature::plan(
+                &feature.feature,
+                Some(feature_options(feature)),
+                feature.remote_user.as_deref(),
+                Some(feature.envs.clone()),
+            )?);
+        }
+
+        for release in &self.gh_release {
+            plan.releases
+                .push(gh_release::plan(&release_config(release))?);
+        }
+
+        for run in &self.x {
+            plan.runs
+                .push(x::plan(&run_config(run, OutputFormat::Text))?);
+        }
+
+        Ok(plan)
+    }
+
+    fn apply_steps(
+        &self,
+        session: &Session,
+        steps: &[PackageStep],
+        force: bool,
+        output: OutputFormat,
+    ) -> Result<ManifestInstall> {
+        let mut installed = ManifestInstall::default();
+
+        for (manager, packages) in steps {
+            let skipped = if force {
+                None
+            } else {
+                package_manager::already_installed(manager.as_ref(), packages)
+            };
+            installed.packages.push(match skipped {
+                Some(skipped) => skipped,
+                None => package_manager::update_and_install(manager.as_ref(), packages)?,
+            });
+        }
+
+        for feature in &self.devcontainer_feature {
+            installed.features.push(devcontainer_feature::install_in(
+                session,
+                &feature.feature,
+                Some(feature_options(feature)),
+                feature.remote_user.as_deref(),
+                Some(feature.envs.clone()),
+                force,
+            )?);
+        }
+
+        for release in &self.gh_release {
+            let config = gh_release::GhReleaseConfig {
+                force,
+                ..release_config(release)
+            };
+            installed
+                .releases
+                .push(gh_release::install_in(session, &config)?);
+        }
+
+        for run in &self.x {
+            installed
+                .runs
+                .push(x::execute_in(session, &run_config(run, output))?);
+        }
+
+        Ok(installed)
+    }
+
+    /// Package managers and the packages each installs, in manifest order
+    fn package_steps(&self) -> Result<Vec<PackageStep>> {
+        let mut steps: Vec<PackageStep> = Vec::new();
+
+        if let Some(section) = &self.pkg {
+            let mappings = section
+                .map
+                .iter()
+                .map(|m| m.parse())
+                .collect::<Result<Vec<PackageMapping>>>()?;
+            let distro = os_detect::detect_distro()?;
+            let packages = pkg::resolve_packages(&section.packages, &mappings, &distro);
+            steps.push((pkg::native_package_manager(&distro)?, packages));
+        }
+
+        if let Some(section) = &self.apt_get {
+            let repositories = section
+                .repos
+                .iter()
+                .map(|r| r.parse())
+                .collect::<Result<Vec<_>>>()?;
+            steps.push((
+                Box::new(AptGet {
+                    ppas: section.ppas.clone(),
+                    force_ppas_on_non_ubuntu: section.force_ppas_on_non_ubuntu,
+                    repositories,
+                }),
+                section.packages.clone(),
+            ));
+        }
+
+        if let Some(section) = &self.apk {
+            steps.push((
+                Box::new(Apk {
+                    repositories: section.repositories.clone(),
+                    keys: section.keys.clone(),
+                }),
+                section.packages.clone(),
+            ));
+        }
+
+        steps.retain(|(manager, packages)| {
+            if packages.is_empty() {
+                warn!(
+                    "No {} packages listed in manifest, skipping",
+                    manager.name()
+                );
+            }
+            !packages.is_empty()
+        });
+        Ok(steps)
+    }
+}
+
+fn clean_up(steps: &[PackageStep], leftovers: &[Leftovers]) -> Result<()> {
+    for ((manager, _), leftovers) in steps.iter().zip(leftovers) {
+        info!("Cleaning up {} cache", manager.name());
+        manager.clean()?;
+        leftovers.remove()?;
+    }
+    Ok(())
+}
+
+impl std::str::FromStr for Manifest {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        toml::from_str(s).context("Failed to parse manifest")
+    }
+}
+
+fn feature_options(feature: &FeatureSection) -> HashMap<String, String> {
+    feature
+        .options
+        .iter()
+        .map(|(key, value)| (key.clone(), option_value(value)))
+        .collect()
+}
+
+fn release_config(release: &GhReleaseSection) -> gh_release::GhReleaseConfig<'_> {
+    gh_release::GhReleaseConfig {
+        repo: &release.repo,
+        binary_names: &release.binary_names,
+        version: &release.version,
+        install_dir: &release.install_dir,
+        filter: release.filter.as_deref(),
+        verify_checksum: release.verify_checksum,
+        checksum_text: release.checksum_text.as_deref(),
+        gpg_key: release.gpg_key.as_deref(),
+        tag_regex: release.tag_regex.as_deref(),
+        include_prereleases: release.include_prereleases,
+        token_file: release.token_file.as_deref(),
+        source: release.source,
+        api_url: release.api_url.as_deref(),
+        force: false,
+    }
+}
+
+fn run_config(run: &RunSection, output: OutputFormat) -> x::RunConfig<'_> {
+    x::RunConfig {
+        tool: &run.tool,
+        args: run.args.clone(),
+        working_dir: &run.working_dir,
+        env_vars: run.env_vars.clone(),
+        output,
+    }
+}
+
+/// Feature option values are passed to install scripts as strings
+fn option_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[apt_get]
+packages = ["curl", "git"]
+repos = ["https://download.docker.com/linux/debian/gpg https://download.docker.com/linux/debian bookworm stable"]
+
+[[devcontainer_feature]]
+feature = "ghcr.io/devcontainers/features/node:1"
+options = { version = "20", installYarn = false }
+
+[[gh_release]]
+repo = "cli/cli"
+binary_names = ["gh"]
+verify_checksum = true
+
+[[x]]
+tool = "python@3.12"
+args = ["--version"]
+"#;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest: Manifest = MANIFEST.parse().unwrap();
+
+        let apt_get = manifest.apt_get.unwrap();
+        assert_eq!(apt_get.packages, vec!["curl", "git"]);
+        assert_eq!(apt_get.repos.len(), 1);
+        assert!(manifest.apk.is_none());
+
+        let feature = &manifest.devcontainer_feature[0];
+        assert_eq!(option_value(&feature.options["version"]), "20");
+        assert_eq!(option_value(&feature.options["installYarn"]), "false");
+
+        let release = &manifest.gh_release[0];
+        assert_eq!(release.version, "latest");
+        assert_eq!(release.install_dir, "/usr/local/bin");
+        assert!(release.verify_checksum);
+
+        assert_eq!(manifest.x[0].working_dir, ".");
+    }
+
+    #[test]
+    fn test_parse_manifest_unknown_field() {
+        let result: Result<Manifest> =
+            "[[gh_release]]\nrepo = \"cli/cli\"\nbinaries = [\"gh\"]\n".parse();
+        assert!(result.is_err());
+    }
+}
@@ -1,8 +1,19 @@
 pub mod apk;
 pub mod apt;
 pub mod apt_get;
+pub mod apt_repository;
 pub mod aptitude;
 pub mod brew;
+pub mod build;
 pub mod devcontainer_feature;
+pub mod dnf;
 pub mod gh_release;
+pub mod manifest;
+pub mod package_manager;
+pub mod pacman;
+pub mod pkg;
+pub mod session;
+pub mod upgrade;
+pub mod url;
 pub mod x;
+pub mod zypper;
@@ -0,0 +1,484 @@
+use crate::utils::command::{self, CommandError};
+use crate::utils::leftovers::{Leftover, Leftovers};
+use crate::utils::paths;
+use crate::utils::snapshot::format_size;
+use anyhow::Result;
+use log::{debug, info};
+use serde::Serialize;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A failed package manager step, labelled with the package manager that ran it
+#[derive(Debug, thiserror::Error)]
+pub enum PackageManagerError {
+    #[error("Failed to update {0} repositories")]
+    Update(&'static str, #[source] CommandError),
+
+    #[error("Failed to install {0} packages")]
+    Install(&'static str, #[source] CommandError),
+
+    #[error("Failed to purge {0} packages")]
+    Purge(&'static str, #[source] CommandError),
+
+    #[error("Failed to clean {0} cache")]
+    Clean(&'static str, #[source] CommandError),
+
+    #[error("Failed to add {0} repository")]
+    AddRepository(&'static str, #[source] CommandError),
+
+    #[error("Failed to remove {0} repository")]
+    RemoveRepository(&'static str, #[source] CommandError),
+
+    #[error("Failed to list installed {0} packages")]
+    List(&'static str, #[source] CommandError),
+
+    #[error("Failed to simulate {0} install")]
+    Plan(&'static str, #[source] CommandError),
+
+    #[error("Failed to list files of {0} packages")]
+    Files(&'static str, #[source] CommandError),
+}
+
+/// Package databases, caches and logs that only the package managers may change
+pub const PACKAGE_DATABASE_PATHS: &[&str] = &[
+    "/var/lib/dpkg",
+    "/var/lib/apt",
+    "/var/cache/apt",
+    "/var/cache/debconf",
+    "/var/log/apt",
+    "/var/log/dpkg.log",
+    "/var/lib/rpm",
+    "/usr/lib/sysimage/rpm",
+    "/var/lib/dnf",
+    "/var/cache/dnf",
+    "/var/cache/yum",
+    "/var/lib/zypp",
+    "/var/cache/zypp",
+    "/lib/apk/db",
+    "/var/cache/apk",
+    "/var/lib/pacman",
+    "/var/cache/pacman",
+    "/var/log/pacman.log",
+    "/etc/ld.so.cache",
+];
+
+/// A package installed on the system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+}
+
+/// Packages added or upgraded by an install
+#[derive(Debug, Serialize)]
+pub struct PackageInstall {
+    pub manager: &'static str,
+    pub packages: Vec<InstalledPackage>,
+    /// Why nothing was installed, when every package was already present
+    pub skipped: Option<String>,
+}
+
+/// A package that an install would add or upgrade
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedPackage {
+    pub name: String,
+    /// Version that would be installed, if the package manager reports it
+    pub version: Option<String>,
+    /// Installed or download size in bytes, if the package manager reports it
+    pub size: Option<u64>,
+}
+
+impl PlannedPackage {
+    /// A requested package whose version and size are unknown
+    pub fn requested(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: None,
+            size: None,
+        }
+    }
+}
+
+/// Packages an install would add, without changing the system
+#[derive(Debug, Serialize)]
+pub struct PackagePlan {
+    pub manager: &'static str,
+    pub packages: Vec<PlannedPackage>,
+}
+
+impl PackagePlan {
+    /// Total size of the packages that report one
+    pub fn size(&self) -> u64 {
+        self.packages.iter().filter_map(|p| p.size).sum()
+    }
+}
+
+impl fmt::Display for PackagePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} would install {} packages ({}):",
+            self.manager,
+            self.packages.len(),
+            format_size(self.size() as i64)
+        )?;
+        for package in &self.packages {
+            write!(f, "  {}", package.name)?;
+            if let Some(version) = &package.version {
+                write!(f, " {}", version)?;
+            }
+            if let Some(size) = package.size {
+                write!(f, " ({})", format_size(size as i64))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Common interface over the system package managers
+pub trait PackageManager {
+    /// Name used in logs and error messages
+    fn name(&self) -> &'static str;
+
+    /// Check if this package manager can be used on the current system
+    fn detect(&self) -> bool;
+
+    /// Message shown when the package manager is used on an unsupported system
+    fn unsupported_reason(&self) -> String {
+        format!("{} is not available on this system", self.name())
+    }
+
+    /// Refresh repository metadata
+    fn update(&self) -> Result<()>;
+
+    /// Install packages
+    fn install(&self, packages: &[String]) -> Result<()>;
+
+    /// Remove packages along with dependencies that are no longer needed
+    fn remove(&self, packages: &[String]) -> Result<()>;
+
+    /// Remove caches left behind by updating and installing
+    fn clean(&self) -> Result<()>;
+
+    /// List installed packages with their versions
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>>;
+
+    /// Paths whose entries created by the install are removed after cleaning
+    fn leftovers(&self) -> &'static [Leftover] {
+        &[]
+    }
+
+    /// Files the given installed packages own
+    fn package_files(&self, packages: &[String]) -> Result<Vec<PathBuf>> {
+        debug!(
+            "{} does not list package files for {:?}",
+            self.name(),
+            packages
+        );
+        Ok(Vec::new())
+    }
+
+    /// Simulate installing packages, reporting what would be added
+    fn plan(&self, packages: &[String]) -> Result<Vec<PlannedPackage>> {
+        Ok(packages
+            .iter()
+            .map(|p| PlannedPackage::requested(p))
+            .collect())
+    }
+}
+
+/// Report what installing packages would do without changing the system
+pub fn plan(manager: &dyn PackageManager, packages: &[String]) -> Result<PackagePlan> {
+    anyhow::ensure!(manager.detect(), manager.unsupported_reason());
+    Ok(PackagePlan {
+        manager: manager.name(),
+        packages: manager.plan(packages)?,
+    })
+}
+
+/// Update repositories, install packages and clean up afterwards
+pub fn install(manager: &dyn PackageManager, packages: &[String]) -> Result<PackageInstall> {
+    anyhow::ensure!(manager.detect(), manager.unsupported_reason());
+    let leftovers = Leftovers::capture(manager.leftovers());
+
+    let installed = update_and_install(manager, packages)?;
+
+    info!("Cleaning up {} cache", manager.name());
+    manager.clean()?;
+    leftovers.remove()?;
+    Ok(installed)
+}
+
+/// Update repositories and install packages, leaving caches for the caller to clean
+pub fn update_and_install(
+    manager: &dyn PackageManager,
+    packages: &[String],
+) -> Result<PackageInstall> {
+    let before = match manager.list_installed() {
+        Ok(before) => Some(before),
+        Err(e) => {
+            debug!("Not reporting installed packages: {:#}", e);
+            None
+        }
+    };
+
+    info!("Updating {} repositories", manager.name());
+    manager.update()?;
+
+    info!("Installing {} packages: {:?}", manager.name(), packages);
+    manager.install(packages)?;
+
+    let packages = match before {
+        Some(before) => changed(&before, &manager.list_installed()?),
+        None => Vec::new(),
+    };
+    Ok(PackageInstall {
+        manager: manager.name(),
+        packages,
+        skipped: None,
+    })
+}
+
+/// Report the install as skipped when every requested package is already installed,
+/// at the pinned version for `name=version` requests
+pub fn already_installed(
+    manager: &dyn PackageManager,
+    packages: &[String],
+) -> Option<PackageInstall> {
+    let installed = manager.list_installed().ok()?;
+    let found = satisfied_by(&installed, packages)?;
+    let reason = format!(
+        "{} packages already installed: {} (use --force to reinstall)",
+        manager.name(),
+        packages.join(", ")
+    );
+    info!("{}", reason);
+    Some(PackageInstall {
+        manager: manager.name(),
+        packages: found,
+        skipped: Some(reason),
+    })
+}
+
+/// The installed packages matching every request, or `None` if any request is not satisfied
+fn satisfied_by(
+    installed: &[InstalledPackage],
+    packages: &[String],
+) -> Option<Vec<InstalledPackage>> {
+    if packages.is_empty() {
+        return None;
+    }
+    packages
+        .iter()
+        .map(|request| {
+            let (name, version) = match request.split_once('=') {
+                Some((name, version)) => (name, Some(version)),
+                None => (request.as_str(), None),
+            };
+            // apk requests may name a tagged repository, e.g. `curl@edge`
+            let name = name.split('@').next().unwrap_or(name);
+            installed
+                .iter()
+                .find(|p| p.name == name && version.is_none_or(|v| p.version == v))
+                .cloned()
+        })
+        .collect()
+}
+
+/// Packages in `after` that are new or have a different version than in `before`
+pub fn changed(before: &[InstalledPackage], after: &[InstalledPackage]) -> Vec<InstalledPackage> {
+    after
+        .iter()
+        .filter(|package| !before.contains(package))
+        .cloned()
+        .collect()
+}
+
+/// Names of packages in `after` that were not installed in `before`
+pub fn newly_installed(before: &[InstalledPackage], after: &[InstalledPackage]) -> Vec<String> {
+    after
+        .iter()
+        .filter(|package| !before.iter().any(|p| p.name == package.name))
+        .map(|package| package.name.clone())
+        .collect()
+}
+
+/// Whether a path lies under one of the package manager databases or caches
+pub fn is_package_database(path: &Path) -> bool {
+    PACKAGE_DATABASE_PATHS
+        .iter()
+        .any(|database| path.starts_with(database))
+}
+
+/// Remove cache files and directories matching the given path patterns
+pub(crate) fn remove_paths(manager: &'static str, patterns: &[&str]) -> Result<()> {
+    let paths: Vec<PathBuf> = patterns.iter().flat_map(|p| paths::expand(p)).collect();
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut cmd = std::process::Command::new("sudo");
+    cmd.arg("rm").arg("-rf").arg("--").args(&paths);
+    command::run(cmd).map_err(|e| PackageManagerError::Clean(manager, e))?;
+    Ok(())
+}
+
+/// Write a world-readable file under root-owned package manager configuration
+pub(crate) fn install_file(contents: &[u8], path: &Path) -> Result<()> {
+    let mut staged = tempfile::NamedTempFile::new()?;
+    staged.write_all(contents)?;
+
+    let mut cmd = std::process::Command::new("sudo");
+    cmd.arg("install")
+        .arg("-D")
+        .arg("-m")
+        .arg("0644")
+        .arg(staged.path())
+        .arg(path);
+    command::run(cmd)?;
+    Ok(())
+}
+
+/// Parse `name<separator>version` lines into installed packages
+pub(crate) fn parse_installed(output: &str, separator: char) -> Vec<InstalledPackage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, version) = line.trim().split_once(separator)?;
+            Some(InstalledPackage {
+                name: name.trim().to_string(),
+                version: version.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parse a package file listing with one absolute path per line, skipping the root,
+/// directories marked with a trailing slash and notes such as `(contains no files)`
+pub(crate) fn parse_file_list(output: &str) -> Vec<PathBuf> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with('/') && !line.ends_with('/') && *line != "/.")
+        .map(PathBuf::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_installed() {
+        let packages = parse_installed("curl\t7.88.1-10\nbash\t5.2.15-2\n\nbroken\n", '\t');
+        assert_eq!(
+            packages,
+            vec![
+                InstalledPackage {
+                    name: "curl".to_string(),
+                    version: "7.88.1-10".to_string()
+                },
+                InstalledPackage {
+                    name: "bash".to_string(),
+                    version: "5.2.15-2".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_newly_installed() {
+        let package = |name: &str| InstalledPackage {
+            name: name.to_string(),
+            version: "1.0".to_string(),
+        };
+        let before = vec![package("bash"), package("curl")];
+        let after = vec![
+            package("bash"),
+            package("curl"),
+            package("gcc"),
+            package("make"),
+        ];
+        assert_eq!(
+            newly_installed(&before, &after),
+            vec!["gcc".to_string(), "make".to_string()]
+        );
+        assert!(newly_installed(&after, &before).is_empty());
+    }
+
+    #[test]
+    fn test_changed() {
+        let package = |name: &str, version: &str| InstalledPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+        };
+        let before = vec![package("bash", "5.2"), package("curl", "7.88")];
+        let after = vec![
+            package("bash", "5.2"),
+            package("curl", "8.0"),
+            package("git", "2.39"),
+        ];
+        assert_eq!(
+            changed(&before, &after),
+            vec![package("curl", "8.0"), package("git", "2.39")]
+        );
+    }
+
+    #[test]
+    fn test_satisfied_by() {
+        let package = |name: &str, version: &str| InstalledPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+        };
+        let installed = vec![package("curl", "7.88"), package("git", "2.39")];
+        let requests =
+            |requests: &[&str]| -> Vec<String> { requests.iter().map(|r| r.to_string()).collect() };
+
+        assert_eq!(
+            satisfied_by(&installed, &requests(&["curl", "git=2.39"])),
+            Some(installed.clone())
+        );
+        assert_eq!(
+            satisfied_by(&installed, &requests(&["curl@edge"])),
+            Some(vec![package("curl", "7.88")])
+        );
+        assert!(satisfied_by(&installed, &requests(&["curl", "make"])).is_none());
+        assert!(satisfied_by(&installed, &requests(&["git=2.40"])).is_none());
+        assert!(satisfied_by(&installed, &[]).is_none());
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        assert_eq!(
+            parse_file_list("/.\n/usr/bin/curl\n/usr/share/doc/curl/\n(contains no files)\n\n"),
+            vec![PathBuf::from("/usr/bin/curl")]
+        );
+        assert!(is_package_database(Path::new(
+            "/var/lib/dpkg/info/curl.list"
+        )));
+        assert!(!is_package_database(Path::new("/var/lib/dpkgx/file")));
+        assert!(!is_package_database(Path::new("/usr/local/bin/node")));
+    }
+
+    #[test]
+    fn test_package_plan_display() {
+        let plan = PackagePlan {
+            manager: "apt-get",
+            packages: vec![
+                PlannedPackage {
+                    name: "curl".to_string(),
+                    version: Some("7.88.1-10".to_string()),
+                    size: Some(512 * 1024),
+                },
+                PlannedPackage::requested("git"),
+            ],
+        };
+        assert_eq!(plan.size(), 512 * 1024);
+        assert_eq!(
+            plan.to_string(),
+            "apt-get would install 2 packages (512.0 KiB):\n  curl 7.88.1-10 (512.0 KiB)\n  git\n"
+        );
+    }
+}
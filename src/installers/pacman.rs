@@ -0,0 +1,199 @@
+use crate::installers::package_manager::{
+    self, InstalledPackage, PackageInstall, PackageManager, PackageManagerError, PlannedPackage,
+};
+use crate::utils;
+use crate::utils::command;
+use anyhow::Result;
+use log::debug;
+use std::path::{Path, PathBuf};
+
+/// Sync databases fetched by `pacman -Sy`
+const PACMAN_SYNC_DIR: &str = "/var/lib/pacman/sync";
+
+/// Package cache and sync databases removed after installing
+const PACMAN_CACHE_PATHS: &[&str] = &["/var/cache/pacman/pkg/*", "/var/lib/pacman/sync/*"];
+
+/// Install packages using pacman
+pub fn install(packages: &[String]) -> Result<PackageInstall> {
+    package_manager::install(&Pacman, packages)
+}
+
+/// pacman backend for Arch Linux and its derivatives
+#[derive(Debug, Default)]
+pub struct Pacman;
+
+impl PackageManager for Pacman {
+    fn name(&self) -> &'static str {
+        "pacman"
+    }
+
+    fn detect(&self) -> bool {
+        utils::os_detect::is_arch_like() && which::which("pacman").is_ok()
+    }
+
+    fn unsupported_reason(&self) -> String {
+        "pacman should be used on Arch Linux distributions (Arch, Manjaro, etc.)".to_string()
+    }
+
+    fn update(&self) -> Result<()> {
+        command::run(pacman_sync()).map_err(|e| PackageManagerError::Update("pacman", e))?;
+        Ok(())
+    }
+
+    fn install(&self, packages: &[String]) -> Result<()> {
+        debug!("Installing pacman packages: {:?}", packages);
+        command::run(pacman_install(packages))
+            .map_err(|e| PackageManagerError::Install("pacman", e))?;
+        Ok(())
+    }
+
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        command::run(pacman_remove(packages))
+            .map_err(|e| PackageManagerError::Purge("pacman", e))?;
+        Ok(())
+    }
+
+    /// The package cache is removed outright, so `pacman -Sc` would not free anything more
+    fn clean(&self) -> Result<()> {
+        package_manager::remove_paths("pacman", PACMAN_CACHE_PATHS)
+    }
+
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
+        let mut cmd = std::process::Command::new("pacman");
+        cmd.arg("-Q");
+        let output = command::run(cmd).map_err(|e| PackageManagerError::List("pacman", e))?;
+
+        Ok(package_manager::parse_installed(
+            &String::from_utf8_lossy(&output.stdout),
+            ' ',
+        ))
+    }
+
+    fn package_files(&self, packages: &[String]) -> Result<Vec<PathBuf>> {
+        if packages.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut cmd = std::process::Command::new("pacman");
+        cmd.arg("-Qlq").args(packages);
+        let output = command::run(cmd).map_err(|e| PackageManagerError::Files("pacman", e))?;
+
+        Ok(package_manager::parse_file_list(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    fn plan(&self, packages: &[String]) -> Result<Vec<PlannedPackage>> {
+        anyhow::ensure!(
+            has_sync_databases(Path::new(PACMAN_SYNC_DIR)),
+            "No pacman sync databases in {}, so nothing can be planned; run `pacman -Sy` first",
+            PACMAN_SYNC_DIR
+        );
+
+        let mut cmd = std::process::Command::new("pacman");
+        cmd.arg("-Sp")
+            .arg("--needed")
+            .arg("--print-format")
+            .arg("%n %v %s")
+            .args(packages);
+        let output = command::run(cmd).map_err(|e| PackageManagerError::Plan("pacman", e))?;
+        Ok(parse_pacman_plan(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+/// Whether `pacman -Sy` has fetched any sync database into `dir`
+fn has_sync_databases(dir: &Path) -> bool {
+    std::fs::read_dir(dir).is_ok_and(|entries| {
+        entries
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.path().extension().is_some_and(|ext| ext == "db"))
+    })
+}
+
+/// Parse `pacman -Sp --print-format "%n %v %s"` lines with download sizes in bytes
+fn parse_pacman_plan(output: &str) -> Vec<PlannedPackage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let version = fields.next()?;
+            Some(PlannedPackage {
+                name: name.to_string(),
+                version: Some(version.to_string()),
+                size: fields.next().and_then(|s| s.parse().ok()),
+            })
+        })
+        .collect()
+}
+
+fn pacman() -> std::process::Command {
+    let mut cmd = std::process::Command::new("sudo");
+    cmd.arg("pacman");
+    cmd
+}
+
+fn pacman_sync() -> std::process::Command {
+    let mut cmd = pacman();
+    cmd.arg("-Sy").arg("--noconfirm");
+    cmd
+}
+
+/// Databases are synced by `update`, so installing does not refresh them again
+fn pacman_install(packages: &[String]) -> std::process::Command {
+    let mut cmd = pacman();
+    cmd.arg("-S")
+        .arg("--noconfirm")
+        .arg("--needed")
+        .args(packages);
+    cmd
+}
+
+fn pacman_remove(packages: &[String]) -> std::process::Command {
+    let mut cmd = pacman();
+    cmd.arg("-Rns").arg("--noconfirm").args(packages);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_parse_pacman_plan() {
+        let planned = parse_pacman_plan("curl 8.9.1-2 1234567\nlibnghttp3 1.4.0-1 75000\n");
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].name, "curl");
+        assert_eq!(planned[0].version.as_deref(), Some("8.9.1-2"));
+        assert_eq!(planned[0].size, Some(1234567));
+    }
+
+    #[test]
+    #[serial]
+    fn test_has_sync_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("core.db.sig"), "").unwrap();
+        assert!(!has_sync_databases(dir.path()));
+        assert!(!has_sync_databases(&dir.path().join("missing")));
+
+        std::fs::write(dir.path().join("core.db"), "").unwrap();
+        assert!(has_sync_databases(dir.path()));
+    }
+
+    #[test]
+    #[serial]
+    fn test_pacman_install_command() {
+        let cmd = pacman_install(&["curl".to_string()]);
+        let description = command::describe(&cmd);
+        assert!(description.contains("pacman -S --noconfirm --needed curl"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_pacman() {
+        let packages = vec!["curl".to_string()];
+        let result = install(&packages);
+        let _ = result;
+    }
+}
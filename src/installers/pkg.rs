@@ -0,0 +1,287 @@
+use crate::installers::package_manager::{self, PackageInstall, PackageManager, PackagePlan};
+use crate::installers::{apk, apt_get, dnf, pacman, zypper};
+use crate::utils::os_detect::{self, LinuxDistro};
+use crate::utils::receipts::{self, Receipt};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::str::FromStr;
+
+/// Equivalent package names across distributions, e.g. `alpine:build-base=debian:build-essential`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageMapping {
+    names: Vec<(String, String)>,
+}
+
+impl PackageMapping {
+    /// Package name for the first matching distribution name
+    fn name_for(&self, distro_names: &[&str]) -> Option<&str> {
+        distro_names.iter().find_map(|distro| {
+            self.names
+                .iter()
+                .find(|(d, _)| d == distro)
+                .map(|(_, pkg)| pkg.as_str())
+        })
+    }
+
+    fn contains(&self, package: &str) -> bool {
+        self.names.iter().any(|(_, pkg)| pkg == package)
+    }
+}
+
+impl FromStr for PackageMapping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let known: Vec<&str> = LinuxDistro::SUPPORTED
+            .iter()
+            .flat_map(|d| d.names().iter().copied())
+            .collect();
+
+        let names = s
+            .split('=')
+            .map(|entry| {
+                let (distro, package) = entry.trim().split_once(':').with_context(|| {
+                    format!(
+                        "Invalid package mapping entry: {} (expected distro:package)",
+                        entry
+                    )
+                })?;
+                let distro = distro.trim().to_ascii_lowercase();
+                anyhow::ensure!(
+                    known.contains(&distro.as_str()),
+                    "Unknown distribution '{}' in package mapping (expected one of: {})",
+                    distro,
+                    known.join(", ")
+                );
+                anyhow::ensure!(
+                    !package.trim().is_empty(),
+                    "Empty package name in package mapping: {}",
+                    s
+                );
+                Ok((distro, package.trim().to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        anyhow::ensure!(
+            names.len() >= 2,
+            "Package mapping must name at least two distributions: {}",
+            s
+        );
+
+        Ok(Self { names })
+    }
+}
+
+/// Select the native package manager for a distribution
+pub fn native_package_manager(distro: &LinuxDistro) -> Result<Box<dyn PackageManager>> {
+    match distro {
+        LinuxDistro::Ubuntu | LinuxDistro::Debian => Ok(Box::new(apt_get::AptGet::default())),
+        LinuxDistro::Alpine => Ok(Box::new(apk::Apk::default())),
+        LinuxDistro::Fedora
+        | LinuxDistro::Rhel
+        | LinuxDistro::CentOs
+        | LinuxDistro::Rocky
+        | LinuxDistro::Alma => Ok(Box::new(dnf::Dnf)),
+        LinuxDistro::OpenSuseLeap | LinuxDistro::OpenSuseTumbleweed | LinuxDistro::Sles => {
+            Ok(Box::new(zypper::Zypper))
+        }
+        LinuxDistro::Arch => Ok(Box::new(pacman::Pacman)),
+        LinuxDistro::Other => anyhow::bail!(
+            "Could not detect a supported Linux distribution. \
+             Use a specific package manager command (e.g., apt-get, apk, dnf, zypper or pacman) instead."
+        ),
+    }
+}
+
+/// Replace package names with their equivalents on the given distribution
+pub fn resolve_packages(
+    packages: &[String],
+    mappings: &[PackageMapping],
+    distro: &LinuxDistro,
+) -> Vec<String> {
+    packages
+        .iter()
+        .map(|package| {
+            mappings
+                .iter()
+                .filter(|mapping| mapping.contains(package))
+                .find_map(|mapping| mapping.name_for(distro.names()))
+                .unwrap_or(package)
+                .to_string()
+        })
+        .collect()
+}
+
+/// Install packages with the package manager native to the detected distribution
+pub fn install(packages: &[String], mappings: &[PackageMapping]) -> Result<PackageInstall> {
+    let distro = os_detect::detect_distro()?;
+    let manager = native_package_manager(&distro)?;
+    let packages = resolve_packages(packages, mappings, &distro);
+    info!(
+        "Detected {:?}, installing with {}: {:?}",
+        distro,
+        manager.name(),
+        packages
+    );
+
+    package_manager::install(manager.as_ref(), &packages)
+}
+
+/// Report what installing packages with the native package manager would add
+pub fn plan(packages: &[String], mappings: &[PackageMapping]) -> Result<PackagePlan> {
+    let distro = os_detect::detect_distro()?;
+    let manager = native_package_manager(&distro)?;
+    let packages = resolve_packages(packages, mappings, &distro);
+    package_manager::plan(manager.as_ref(), &packages)
+}
+
+/// Remove the packages an install recorded with the native package manager,
+/// keeping those another recorded install also added, and return the removed names
+pub fn remove_recorded(receipt: &Receipt) -> Result<Vec<String>> {
+    if receipt.packages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let others = receipts::list()?.0;
+    let packages = unshared_packages(receipt, &others);
+    if packages.is_empty() {
+        return Ok(packages);
+    }
+
+    let manager = native_package_manager(&os_detect::detect_distro()?)?;
+    if let Some(package) = receipt
+        .packages
+        .iter()
+        .find(|package| package.manager != manager.name())
+    {
+        anyhow::bail!(
+            "{} was installed with {}, but {} is the package manager on this system",
+            package.name,
+            package.manager,
+            manager.name()
+        );
+    }
+
+    info!("Removing {} packages: {:?}", manager.name(), packages);
+    manager.remove(&packages)?;
+    Ok(packages)
+}
+
+/// Names of the receipt's packages that no other receipt lists
+fn unshared_packages(receipt: &Receipt, others: &[Receipt]) -> Vec<String> {
+    receipt
+        .packages
+        .iter()
+        .filter(|package| {
+            let shared = others
+                .iter()
+                .find(|other| other.name != receipt.name && other.packages.contains(package));
+            if let Some(other) = shared {
+                warn!(
+                    "Keeping {}, which {} also installed",
+                    package.name, other.name
+                );
+            }
+            shared.is_none()
+        })
+        .map(|package| package.name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::receipts::ReceiptPackage;
+
+    #[test]
+    fn test_unshared_packages() {
+        let receipt = |name: &str, packages: &[&str]| {
+            let mut receipt = Receipt::new(name, "devcontainer-feature", name, "1.0.0");
+            receipt.packages = packages
+                .iter()
+                .map(|package| ReceiptPackage {
+                    manager: "apt-get".to_string(),
+                    name: package.to_string(),
+                })
+                .collect();
+            receipt
+        };
+        let node = receipt("node", &["gnupg", "libatomic1"]);
+        let others = vec![node.clone(), receipt("python", &["gnupg", "libffi-dev"])];
+
+        assert_eq!(
+            unshared_packages(&node, &others),
+            vec!["libatomic1".to_string()]
+        );
+        assert_eq!(
+            unshared_packages(&node, std::slice::from_ref(&node)),
+            vec!["gnupg".to_string(), "libatomic1".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_mapping() {
+        let mapping: PackageMapping = "alpine:build-base=debian:build-essential".parse().unwrap();
+        assert_eq!(mapping.name_for(&["alpine"]), Some("build-base"));
+        assert_eq!(mapping.name_for(&["debian"]), Some("build-essential"));
+    }
+
+    #[test]
+    fn test_parse_mapping_invalid() {
+        assert!("alpine:build-base".parse::<PackageMapping>().is_err());
+        assert!(
+            "build-base=build-essential"
+                .parse::<PackageMapping>()
+                .is_err()
+        );
+        assert!("gentoo:gcc=debian:gcc".parse::<PackageMapping>().is_err());
+        assert!("alpine:=debian:gcc".parse::<PackageMapping>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_packages() {
+        let mappings = vec![
+            "alpine:build-base=debian:build-essential"
+                .parse::<PackageMapping>()
+                .unwrap(),
+        ];
+        let packages = vec!["build-base".to_string(), "curl".to_string()];
+
+        assert_eq!(
+            resolve_packages(&packages, &mappings, &LinuxDistro::Ubuntu),
+            vec!["build-essential".to_string(), "curl".to_string()]
+        );
+        assert_eq!(
+            resolve_packages(&packages, &mappings, &LinuxDistro::Alpine),
+            vec!["build-base".to_string(), "curl".to_string()]
+        );
+        assert_eq!(
+            resolve_packages(&packages, &mappings, &LinuxDistro::Fedora),
+            vec!["build-base".to_string(), "curl".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_native_package_manager() {
+        assert_eq!(
+            native_package_manager(&LinuxDistro::Debian).unwrap().name(),
+            "apt-get"
+        );
+        assert_eq!(
+            native_package_manager(&LinuxDistro::Alpine).unwrap().name(),
+            "apk"
+        );
+        assert_eq!(
+            native_package_manager(&LinuxDistro::Rocky).unwrap().name(),
+            "dnf"
+        );
+        assert_eq!(
+            native_package_manager(&LinuxDistro::Sles).unwrap().name(),
+            "zypper"
+        );
+        assert_eq!(
+            native_package_manager(&LinuxDistro::Arch).unwrap().name(),
+            "pacman"
+        );
+        assert!(native_package_manager(&LinuxDistro::Other).is_err());
+    }
+}
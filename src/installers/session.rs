@@ -0,0 +1,29 @@
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// HTTP client and temporary directory shared by the installers run in one process
+pub struct Session {
+    client: Client,
+    temp_dir: TempDir,
+}
+
+impl Session {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            client: Client::new(),
+            temp_dir: TempDir::with_prefix("picolayer_")
+                .context("Failed to create temporary directory")?,
+        })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Root under which each installer creates its own scratch directories
+    pub fn temp_dir(&self) -> &Path {
+        self.temp_dir.path()
+    }
+}
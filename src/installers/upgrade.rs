@@ -0,0 +1,234 @@
+use crate::installers::gh_release::{self, Forge, GhReleaseConfig};
+use crate::installers::session::Session;
+use crate::utils::receipts::{self, Receipt};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// A gh-release install whose recorded tag differs from the latest release
+#[derive(Debug, Serialize)]
+pub struct Upgrade {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    /// Binaries installed from the new release, empty when only checking
+    pub binaries: Vec<PathBuf>,
+}
+
+/// Outdated gh-release installs, upgraded unless only checking
+#[derive(Debug, Serialize)]
+pub struct Upgrades {
+    /// Number of installs compared with their latest release
+    pub checked: usize,
+    pub upgraded: bool,
+    pub outdated: Vec<Upgrade>,
+}
+
+impl fmt::Display for Upgrades {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.outdated.is_empty() {
+            return writeln!(f, "All {} gh-release installs are up to date", self.checked);
+        }
+        for upgrade in &self.outdated {
+            if self.upgraded {
+                writeln!(
+                    f,
+                    "Upgraded {} from {} to {}",
+                    upgrade.name, upgrade.from, upgrade.to
+                )?;
+            } else {
+                writeln!(f, "{} {} -> {}", upgrade.name, upgrade.from, upgrade.to)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compare recorded gh-release installs with their latest release, reinstalling
+/// the outdated ones with the settings they were installed with unless `check` is set.
+/// Installs verified only with `--checksum-text` are upgraded unverified, so they need `force`
+pub fn upgrade(
+    name: Option<&str>,
+    check: bool,
+    force: bool,
+    token_file: Option<&str>,
+) -> Result<Upgrades> {
+    let installed: Vec<Receipt> = match name {
+        Some(name) => {
+            let receipt = receipts::find(name)?;
+            anyhow::ensure!(
+                receipt.source == gh_release::RECEIPT_SOURCE,
+                "{} was not installed from a GitHub release",
+                name
+            );
+            vec![receipt]
+        }
+        None => receipts::list()?
+            .0
+            .into_iter()
+            .filter(|receipt| receipt.source == gh_release::RECEIPT_SOURCE)
+            .collect(),
+    };
+
+    let session = Session::new()?;
+    let mut upgrades = Upgrades {
+        checked: installed.len(),
+        upgraded: !check,
+        outdated: Vec::new(),
+    };
+
+    for receipt in &installed {
+        let binary_names = binary_names(receipt)?;
+        let config = GhReleaseConfig {
+            token_file,
+            ..release_config(receipt, &binary_names)?
+        };
+        let latest = gh_release::resolve_tag(session.client(), &config)?;
+        if latest == receipt.version {
+            debug!("{} {} is up to date", receipt.name, receipt.version);
+            continue;
+        }
+
+        let mut upgrade = Upgrade {
+            name: receipt.name.clone(),
+            from: receipt.version.clone(),
+            to: latest,
+            binaries: Vec::new(),
+        };
+        if !check {
+            if !verified_on_upgrade(receipt) {
+                anyhow::ensure!(
+                    force,
+                    "{} was installed without a checksum or signature that can verify {}; \
+                     pass --force to upgrade without verification",
+                    upgrade.name,
+                    upgrade.to
+                );
+                warn!(
+                    "Upgrading {} to {} without verifying the download",
+                    upgrade.name, upgrade.to
+                );
+            }
+            info!(
+                "Upgrading {} from {} to {}",
+                upgrade.name, upgrade.from, upgrade.to
+            );
+            let config = GhReleaseConfig {
+                version: &upgrade.to,
+                force: true,
+                ..config
+            };
+            upgrade.binaries = gh_release::install_in(&session, &config)?.binaries;
+        }
+        upgrades.outdated.push(upgrade);
+    }
+
+    Ok(upgrades)
+}
+
+/// Whether the install was verified with a checksum or signature asset, which the
+/// new release is verified with too. A `--checksum-text` only matches the installed
+/// release, and receipts without a recorded verification are treated as unverified
+fn verified_on_upgrade(receipt: &Receipt) -> bool {
+    receipt
+        .options
+        .get("verification")
+        .is_some_and(|verification| verification == "checksum" || verification == "gpg")
+}
+
+fn binary_names(receipt: &Receipt) -> Result<Vec<String>> {
+    Ok(receipt
+        .options
+        .get("binary_names")
+        .with_context(|| format!("Receipt for {} does not list binary names", receipt.name))?
+        .split(',')
+        .map(String::from)
+        .collect())
+}
+
+/// Settings the release was installed with, resolving the latest release
+fn release_config<'a>(
+    receipt: &'a Receipt,
+    binary_names: &'a [String],
+) -> Result<GhReleaseConfig<'a>> {
+    let option = |key: &str| receipt.options.get(key).map(String::as_str);
+    Ok(GhReleaseConfig {
+        repo: &receipt.reference,
+        binary_names,
+        version: "latest",
+        install_dir: option("install_dir").with_context(|| {
+            format!(
+                "Receipt for {} does not list an install directory",
+                receipt.name
+            )
+        })?,
+        filter: option("filter"),
+        verify_checksum: option("verify_checksum").is_some(),
+        // A checksum given on the command line only matches the release it was given for
+        checksum_text: None,
+        gpg_key: option("gpg_key"),
+        tag_regex: option("tag_regex"),
+        include_prereleases: option("include_prereleases").is_some(),
+        token_file: None,
+        source: option("source")
+            .map(|source| Forge::from_str(source, true).map_err(anyhow::Error::msg))
+            .transpose()?
+            .unwrap_or_default(),
+        api_url: option("api_url"),
+        force: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verified_on_upgrade() {
+        let receipt = |verification: Option<&str>| {
+            let mut receipt = Receipt::new("cli/cli", "gh-release", "cli/cli", "v2.40.0");
+            if let Some(verification) = verification {
+                receipt
+                    .options
+                    .insert("verification".to_string(), verification.to_string());
+            }
+            receipt
+        };
+        assert!(verified_on_upgrade(&receipt(Some("checksum"))));
+        assert!(verified_on_upgrade(&receipt(Some("gpg"))));
+        assert!(!verified_on_upgrade(&receipt(Some("checksum-text"))));
+        assert!(!verified_on_upgrade(&receipt(Some("none"))));
+        assert!(!verified_on_upgrade(&receipt(None)));
+    }
+
+    #[test]
+    fn test_upgrades_display() {
+        let upgrade = || Upgrade {
+            name: "cli/cli".to_string(),
+            from: "v2.40.0".to_string(),
+            to: "v2.50.0".to_string(),
+            binaries: Vec::new(),
+        };
+        let mut upgrades = Upgrades {
+            checked: 2,
+            upgraded: false,
+            outdated: Vec::new(),
+        };
+        assert_eq!(
+            upgrades.to_string(),
+            "All 2 gh-release installs are up to date\n"
+        );
+
+        upgrades.outdated.push(upgrade());
+        assert_eq!(upgrades.to_string(), "cli/cli v2.40.0 -> v2.50.0\n");
+
+        upgrades.upgraded = true;
+        assert_eq!(
+            upgrades.to_string(),
+            "Upgraded cli/cli from v2.40.0 to v2.50.0\n"
+        );
+    }
+}
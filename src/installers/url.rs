@@ -0,0 +1,434 @@
+use crate::installers::gh_release::{AssetInstaller, AssetVerifier, ChecksumSource, GpgVerifier};
+use crate::installers::session::Session;
+use crate::utils::download::{self, Download, Downloader};
+use crate::utils::receipts::{self, Receipt, ReceiptFile};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use reqwest::Url;
+use reqwest::blocking::Client;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Source recorded in the receipts of URL installs
+pub const RECEIPT_SOURCE: &str = "url";
+
+/// Configuration for installing binaries from a URL
+pub struct UrlConfig<'a> {
+    /// URL of the asset, with optional `{os}`, `{arch}` and `{version}` placeholders;
+    /// `file://` URLs install a local file
+    pub url: &'a str,
+    pub binary_names: &'a [String],
+    /// Value of the `{version}` placeholder
+    pub version: Option<&'a str>,
+    pub install_dir: &'a str,
+    pub checksum_text: Option<&'a str>,
+    pub gpg_key: Option<&'a str>,
+    /// URL of the detached signature checked against `gpg_key`, with the same placeholders
+    pub signature_url: Option<&'a str>,
+    /// Reinstall even if the same URL is already installed
+    pub force: bool,
+}
+
+/// The asset an install would download and the binaries it would write
+#[derive(Debug, Serialize)]
+pub struct UrlPlan {
+    pub url: String,
+    pub checksum: ChecksumSource,
+    /// Detached signature URL, checked against `gpg_key`
+    pub signature: Option<String>,
+    pub gpg_key: Option<String>,
+    pub binaries: Vec<PathBuf>,
+}
+
+impl fmt::Display for UrlPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "url would install {}:", self.url)?;
+        writeln!(f, "  checksum: {}", self.checksum)?;
+        if let (Some(signature), Some(gpg_key)) = (&self.signature, &self.gpg_key) {
+            writeln!(f, "  signature: {} (GPG key {})", signature, gpg_key)?;
+        }
+        for binary in &self.binaries {
+            writeln!(f, "  binary: {}", binary.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Binaries installed from a URL
+#[derive(Debug, Serialize)]
+pub struct UrlInstall {
+    pub url: String,
+    pub version: Option<String>,
+    /// Verified hash as `algorithm:hash`
+    pub checksum: Option<String>,
+    /// Verified detached signature URL
+    pub signature: Option<String>,
+    pub binaries: Vec<PathBuf>,
+    /// Why nothing was downloaded, when the URL was already installed
+    pub skipped: Option<String>,
+}
+
+/// Expand the URL placeholders without downloading anything
+pub fn plan(input: &UrlConfig) -> Result<UrlPlan> {
+    let url = expand(input.url, input.version)?;
+    let checksum = match input.checksum_text {
+        Some(text) => ChecksumSource::Text(text.to_string()),
+        None => ChecksumSource::None,
+    };
+    let signature = signature(input)?;
+    Ok(UrlPlan {
+        url,
+        checksum,
+        gpg_key: signature.as_ref().map(|(_, gpg_key)| gpg_key.to_string()),
+        signature: signature.map(|(signature_url, _)| signature_url),
+        binaries: binaries(input),
+    })
+}
+
+/// Install binaries from a URL
+pub fn install(input: &UrlConfig) -> Result<UrlInstall> {
+    install_in(&Session::new()?, input)
+}
+
+/// Install binaries from a URL using a shared session
+pub fn install_in(session: &Session, input: &UrlConfig) -> Result<UrlInstall> {
+    let url = expand(input.url, input.version)?;
+    let signature = signature(input)?;
+    if !input.force
+        && let Some(skipped) = already_installed(input, &url)
+    {
+        return Ok(skipped);
+    }
+
+    let download_dir = tempfile::tempdir_in(session.temp_dir())?;
+    let download = fetch(session.client(), &url, download_dir.path())?;
+
+    let checksum = input
+        .checksum_text
+        .map(|text| {
+            AssetVerifier::new(session.client(), None).verify_with_checksum_text(&download, text)
+        })
+        .transpose()?;
+
+    let signature = signature
+        .map(|(signature_url, gpg_key)| -> Result<String> {
+            info!("Verifying GPG signature");
+            let sig_data = read(session.client(), &signature_url)?;
+            GpgVerifier::new(session.client()).verify_signature(
+                &download.path,
+                sig_data,
+                gpg_key,
+            )?;
+            Ok(signature_url)
+        })
+        .transpose()?;
+
+    info!("Extracting binaries: {}", input.binary_names.join(", "));
+    let binaries = AssetInstaller::new()
+        .in_temp_dir(session.temp_dir())
+        .extract_binaries(&download.path, input.binary_names, input.install_dir)?;
+
+    let installed = UrlInstall {
+        url,
+        version: input.version.map(String::from),
+        checksum,
+        signature,
+        binaries,
+        skipped: None,
+    };
+    if let Err(e) = record_receipt(&installed, input) {
+        warn!("Failed to record receipt for {}: {:#}", installed.url, e);
+    }
+    Ok(installed)
+}
+
+/// The expanded signature URL and the GPG key it is checked against, which are
+/// only meaningful together
+fn signature<'a>(input: &UrlConfig<'a>) -> Result<Option<(String, &'a str)>> {
+    match (input.signature_url, input.gpg_key) {
+        (Some(signature_url), Some(gpg_key)) => {
+            Ok(Some((expand(signature_url, input.version)?, gpg_key)))
+        }
+        (None, None) => Ok(None),
+        (Some(_), None) => anyhow::bail!("--signature-url requires --gpg-key to verify it"),
+        (None, Some(_)) => anyhow::bail!("--gpg-key requires --signature-url to verify with"),
+    }
+}
+
+/// Substitute `{os}`, `{arch}` and `{version}` in a URL template
+fn expand(template: &str, version: Option<&str>) -> Result<String> {
+    let url = template.replace("{os}", os()).replace("{arch}", arch());
+    if !url.contains("{version}") {
+        return Ok(url);
+    }
+    let version = version
+        .with_context(|| format!("{} uses {{version}}, but no --version was given", template))?;
+    Ok(url.replace("{version}", version))
+}
+
+/// Operating system as named in release downloads
+fn os() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    }
+}
+
+/// Architecture as named in release downloads
+fn arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => arch,
+    }
+}
+
+/// Download an HTTP(S) URL to `dir`, or hash a `file://` URL where it lies
+fn fetch(client: &Client, url: &str, dir: &Path) -> Result<Download> {
+    let parsed = Url::parse(url).with_context(|| format!("Invalid URL: {}", url))?;
+    match parsed.scheme() {
+        "file" => {
+            let path = local_path(&parsed)?;
+            let size = fs::metadata(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+                .len();
+            Ok(Download {
+                sha256: receipts::hash_file(&path)?,
+                size,
+                path,
+            })
+        }
+        "http" | "https" => {
+            info!("Downloading {}", url);
+            Downloader::new(client)
+                .download(url, &dir.join(download::file_name(url)))
+                .context("Failed to download asset")
+        }
+        scheme => anyhow::bail!("Unsupported URL scheme: {}", scheme),
+    }
+}
+
+/// Contents of a small file such as a signature, from HTTP(S) or `file://`
+fn read(client: &Client, url: &str) -> Result<Vec<u8>> {
+    let parsed = Url::parse(url).with_context(|| format!("Invalid URL: {}", url))?;
+    if parsed.scheme() == "file" {
+        let path = local_path(&parsed)?;
+        return fs::read(&path).with_context(|| format!("Failed to read {}", path.display()));
+    }
+
+    let response = client
+        .get(url)
+        .header("User-Agent", "picolayer")
+        .send()
+        .with_context(|| format!("Failed to download {}", url))?;
+    if !response.status().is_success() {
+        anyhow::bail!("Failed to download {}: {}", url, response.status());
+    }
+    Ok(response.bytes()?.to_vec())
+}
+
+fn local_path(url: &Url) -> Result<PathBuf> {
+    url.to_file_path()
+        .map_err(|()| anyhow::anyhow!("Invalid file URL: {}", url))
+}
+
+fn binaries(config: &UrlConfig) -> Vec<PathBuf> {
+    config
+        .binary_names
+        .iter()
+        .map(|name| Path::new(config.install_dir).join(name))
+        .collect()
+}
+
+/// Receipts are named after the first binary, e.g. `terraform`
+fn receipt_name(config: &UrlConfig) -> String {
+    config.binary_names.first().cloned().unwrap_or_default()
+}
+
+/// An install recorded for the same URL whose binaries are unchanged
+fn already_installed(config: &UrlConfig, url: &str) -> Option<UrlInstall> {
+    let binaries = binaries(config);
+    let receipt = receipts::get(&receipt_name(config)).ok().flatten()?;
+    let satisfied = receipt.source == RECEIPT_SOURCE
+        && receipt.reference == url
+        && binaries.iter().all(|binary| {
+            binary.is_file() && receipt.files.iter().any(|file| &file.path == binary)
+        })
+        && receipt.files_unchanged();
+    if !satisfied {
+        return None;
+    }
+
+    let reason = format!(
+        "{} is already installed from {} (use --force to reinstall)",
+        receipt.name, url
+    );
+    info!("{}", reason);
+    Some(UrlInstall {
+        url: url.to_string(),
+        version: config.version.map(String::from),
+        checksum: receipt.checksum,
+        signature: None,
+        binaries,
+        skipped: Some(reason),
+    })
+}
+
+fn record_receipt(installed: &UrlInstall, config: &UrlConfig) -> Result<()> {
+    let mut receipt = Receipt::new(
+        &receipt_name(config),
+        RECEIPT_SOURCE,
+        &installed.url,
+        installed.version.as_deref().unwrap_or("unknown"),
+    );
+    receipt.checksum = installed.checksum.clone();
+    receipt
+        .options
+        .insert("binary_names".to_string(), config.binary_names.join(","));
+    receipt
+        .options
+        .insert("install_dir".to_string(), config.install_dir.to_string());
+    if let Some(signature) = &installed.signature {
+        receipt
+            .options
+            .insert("signature_url".to_string(), signature.clone());
+    }
+    receipt.files = installed
+        .binaries
+        .iter()
+        .map(|binary| ReceiptFile::hashed(binary))
+        .collect::<Result<_>>()?;
+    receipts::record(&receipt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    fn test_expand() {
+        let url = expand(
+            "https://releases.example.com/tool/{version}/tool_{version}_{os}_{arch}.zip",
+            Some("1.2.3"),
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            format!(
+                "https://releases.example.com/tool/1.2.3/tool_1.2.3_{}_{}.zip",
+                os(),
+                arch()
+            )
+        );
+        assert!(!url.contains("x86_64") && !url.contains("macos"));
+
+        assert_eq!(
+            expand("file:///srv/tool.tar.gz", None).unwrap(),
+            "file:///srv/tool.tar.gz"
+        );
+        assert!(expand("https://example.com/{version}/tool", None).is_err());
+    }
+
+    #[test]
+    fn test_plan_signature() {
+        let binary_names = vec!["tool".to_string()];
+        let config = UrlConfig {
+            url: "https://releases.example.com/tool-{version}.tar.gz",
+            binary_names: &binary_names,
+            version: Some("1.0"),
+            install_dir: "/usr/local/bin",
+            checksum_text: None,
+            gpg_key: Some("https://releases.example.com/key.asc"),
+            signature_url: Some("https://releases.example.com/tool-{version}.tar.gz.asc"),
+            force: false,
+        };
+
+        let planned = plan(&config).unwrap();
+        assert_eq!(
+            planned.signature.as_deref(),
+            Some("https://releases.example.com/tool-1.0.tar.gz.asc")
+        );
+        assert!(planned.to_string().contains(
+            "signature: https://releases.example.com/tool-1.0.tar.gz.asc \
+             (GPG key https://releases.example.com/key.asc)"
+        ));
+
+        let unsigned = UrlConfig {
+            signature_url: None,
+            ..config
+        };
+        assert!(plan(&unsigned).is_err());
+        assert!(install(&unsigned).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn test_install_from_file_url() {
+        use std::io::Write;
+
+        let script = b"#!/bin/sh\necho tool\n";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(script).unwrap();
+        let asset = encoder.finish().unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let asset_path = dir.path().join("tool-1.0.gz");
+        fs::write(&asset_path, &asset).unwrap();
+        let state_dir = dir.path().join("state");
+        let bin = dir.path().join("bin");
+        unsafe {
+            std::env::set_var("PICOLAYER_STATE_DIR", &state_dir);
+        }
+
+        let url = Url::from_file_path(dir.path()).unwrap();
+        let template = format!("{}/tool-{{version}}.gz", url.as_str().trim_end_matches('/'));
+        let binary_names = vec!["tool".to_string()];
+        let sha256 = receipts::hash_file(&asset_path).unwrap();
+        let checksum_text = format!("sha256:{}", sha256);
+        let config = UrlConfig {
+            url: &template,
+            binary_names: &binary_names,
+            version: Some("1.0"),
+            install_dir: bin.to_str().unwrap(),
+            checksum_text: Some(&checksum_text),
+            gpg_key: None,
+            signature_url: None,
+            force: false,
+        };
+
+        let session = Session::new().unwrap();
+        let installed = install_in(&session, &config).unwrap();
+        assert_eq!(installed.binaries, vec![bin.join("tool")]);
+        assert_eq!(installed.checksum.as_deref(), Some(checksum_text.as_str()));
+        assert!(installed.skipped.is_none());
+        assert_eq!(fs::read(bin.join("tool")).unwrap(), script);
+        // The local asset is read in place, not moved
+        assert!(asset_path.exists());
+
+        let receipt = receipts::get("tool").unwrap().unwrap();
+        assert_eq!(receipt.source, RECEIPT_SOURCE);
+        assert_eq!(receipt.reference, installed.url);
+        assert_eq!(receipt.version, "1.0");
+
+        let again = install_in(&session, &config).unwrap();
+        assert!(again.skipped.is_some());
+
+        let mismatch = format!("sha256:{}", "0".repeat(64));
+        let result = install_in(
+            &session,
+            &UrlConfig {
+                checksum_text: Some(&mismatch),
+                force: true,
+                ..config
+            },
+        );
+        assert!(result.is_err());
+
+        unsafe {
+            std::env::remove_var("PICOLAYER_STATE_DIR");
+        }
+    }
+}
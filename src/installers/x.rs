@@ -1,9 +1,12 @@
 use anyhow::{Context, Result};
 use log::info;
+use serde::Serialize;
 use std::path::Path;
-use std::{collections::HashMap, env};
+use std::{collections::HashMap, env, fmt};
 use tempfile::TempDir;
 
+use crate::installers::session::Session;
+use crate::utils::output::OutputFormat;
 use crate::utils::pkgx;
 
 pub struct RunConfig<'a> {
@@ -11,9 +14,58 @@ pub struct RunConfig<'a> {
     pub args: Vec<String>,
     pub working_dir: &'a str,
     pub env_vars: Vec<String>,
+    /// Where the tool's stdout goes
+    pub output: OutputFormat,
 }
 
-pub fn execute(input: &RunConfig) -> Result<()> {
+/// A tool and the command it would run, without resolving or downloading it
+#[derive(Debug, Serialize)]
+pub struct RunPlan {
+    pub tool: String,
+    pub version: String,
+    pub args: Vec<String>,
+    pub working_dir: String,
+    pub env_vars: Vec<(String, String)>,
+}
+
+impl fmt::Display for RunPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "x would run {} ({}):", self.tool, self.version)?;
+        writeln!(f, "  command: {}", self.args.join(" "))?;
+        writeln!(f, "  working directory: {}", self.working_dir)?;
+        for (key, value) in &self.env_vars {
+            writeln!(f, "  env: {}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn plan(input: &RunConfig) -> Result<RunPlan> {
+    validate_working_directory(input.working_dir)?;
+    let (tool, version) = parse_tool_spec(input.tool);
+    Ok(RunPlan {
+        tool,
+        version,
+        args: input.args.clone(),
+        working_dir: input.working_dir.to_string(),
+        env_vars: parse_env_vars(&input.env_vars)?,
+    })
+}
+
+/// A tool whose command ran successfully
+#[derive(Debug, Serialize)]
+pub struct RunResult {
+    pub tool: String,
+    pub version: String,
+    pub args: Vec<String>,
+}
+
+pub fn execute(input: &RunConfig) -> Result<RunResult> {
+    execute_in(&Session::new()?, input)
+}
+
+/// Run a command using pkgx with its virtual environment inside a shared session
+pub fn execute_in(session: &Session, input: &RunConfig) -> Result<RunResult> {
     validate_working_directory(input.working_dir)?;
     let (tool_name, version_spec) = parse_tool_spec(input.tool);
     info!("Working directory: {}", input.working_dir);
@@ -22,8 +74,8 @@ pub fn execute(input: &RunConfig) -> Result<()> {
 
     let env_map = parse_env_vars(&input.env_vars)?;
 
-    let _temp_dir =
-        TempDir::with_prefix("picolayer_").context("Failed to create temporary directory")?;
+    let _temp_dir = TempDir::with_prefix_in("picolayer_", session.temp_dir())
+        .context("Failed to create temporary directory")?;
 
     // Create virtual environment directory structure
     let pkgx_dir = _temp_dir.path().join("x").join("pkgx");
@@ -65,23 +117,29 @@ pub fn execute(input: &RunConfig) -> Result<()> {
         execute_with_pkgx_binary(
             &tool_name,
             &version_spec,
-            &input.args,
+            input,
             working_path,
             &env_map,
             pkgx_dir_str,
             pantry_dir_str,
-        )
+        )?;
     } else {
         execute_with_pkgx_library(
             &tool_name,
             &version_spec,
-            &input.args,
+            input,
             working_path,
             &env_map,
             pkgx_dir_str,
             pantry_dir_str,
-        )
+        )?;
     }
+
+    Ok(RunResult {
+        tool: tool_name,
+        version: version_spec,
+        args: input.args.clone(),
+    })
 }
 
 fn validate_working_directory(working_dir: &str) -> Result<()> {
@@ -138,7 +196,7 @@ fn create_command_env(
 fn execute_with_pkgx_library(
     tool_name: &str,
     version_spec: &str,
-    args: &[String],
+    input: &RunConfig,
     working_path: &Path,
     env_map: &[(String, String)],
     pkgx_dir: &str,
@@ -146,7 +204,7 @@ fn execute_with_pkgx_library(
 ) -> Result<()> {
     info!("Using pkgx library integration with virtual environment...");
 
-    if args.is_empty() {
+    if input.args.is_empty() {
         anyhow::bail!("No arguments provided for tool: {}", tool_name);
     }
 
@@ -177,10 +235,10 @@ fn execute_with_pkgx_library(
 
             info!("Resolved package with libpkgx");
             let status = std::process::Command::new(tool_name)
-                .args(args)
+                .args(&input.args)
                 .current_dir(working_path.to_str().context("Invalid working directory")?)
                 .envs(&cmd_env)
-                .stdout(std::process::Stdio::inherit())
+                .stdout(input.output.child_stdout())
                 .stderr(std::process::Stdio::inherit())
                 .status()
                 .context("Failed to execute command with libpkgx")?;
@@ -200,7 +258,7 @@ fn execute_with_pkgx_library(
             execute_with_pkgx_binary(
                 tool_name,
                 version_spec,
-                args,
+                input,
                 working_path,
                 env_map,
                 pkgx_dir,
@@ -213,7 +271,7 @@ fn execute_with_pkgx_library(
 fn execute_with_pkgx_binary(
     tool_name: &str,
     version_spec: &str,
-    args: &[String],
+    input: &RunConfig,
     working_path: &Path,
     env_map: &[(String, String)],
     pkgx_dir: &str,
@@ -236,7 +294,7 @@ fn execute_with_pkgx_binary(
     let mut cmd = std::process::Command::new("pkgx");
     cmd.arg(&project_arg)
         .arg(tool_name)
-        .args(args)
+        .args(&input.args)
         .current_dir(working_path.to_str().context("Invalid working directory")?)
         .env("PKGX_DIR", pkgx_dir) // Set virtual environment directory
         .env("PKGX_PANTRY_DIR", pantry_dir); // Set pantry directory
@@ -247,6 +305,7 @@ fn execute_with_pkgx_binary(
     }
 
     let status = cmd
+        .stdout(input.output.child_stdout())
         .status()
         .context("Failed to execute command with pkgx")?;
 
@@ -0,0 +1,182 @@
+use crate::installers::dnf;
+use crate::installers::package_manager::{
+    self, InstalledPackage, PackageInstall, PackageManager, PackageManagerError, PlannedPackage,
+};
+use crate::utils;
+use crate::utils::command;
+use anyhow::Result;
+use log::debug;
+use std::path::PathBuf;
+
+/// Repository metadata and package caches removed after installing
+const ZYPPER_CACHE_PATHS: &[&str] = &[
+    "/var/cache/zypp/raw",
+    "/var/cache/zypp/solv",
+    "/var/cache/zypp/packages",
+];
+
+/// Install packages using zypper
+pub fn install(packages: &[String]) -> Result<PackageInstall> {
+    package_manager::install(&Zypper, packages)
+}
+
+/// zypper backend for openSUSE and SUSE Linux Enterprise
+#[derive(Debug, Default)]
+pub struct Zypper;
+
+impl PackageManager for Zypper {
+    fn name(&self) -> &'static str {
+        "zypper"
+    }
+
+    fn detect(&self) -> bool {
+        utils::os_detect::is_suse_like() && which::which("zypper").is_ok()
+    }
+
+    fn unsupported_reason(&self) -> String {
+        "zypper should be used on openSUSE and SUSE Linux Enterprise distributions".to_string()
+    }
+
+    fn update(&self) -> Result<()> {
+        command::run(zypper_refresh()).map_err(|e| PackageManagerError::Update("zypper", e))?;
+        Ok(())
+    }
+
+    fn install(&self, packages: &[String]) -> Result<()> {
+        debug!("Installing zypper packages: {:?}", packages);
+        command::run(zypper_install(packages))
+            .map_err(|e| PackageManagerError::Install("zypper", e))?;
+        Ok(())
+    }
+
+    fn remove(&self, packages: &[String]) -> Result<()> {
+        command::run(zypper_remove(packages))
+            .map_err(|e| PackageManagerError::Purge("zypper", e))?;
+        Ok(())
+    }
+
+    fn clean(&self) -> Result<()> {
+        command::run(zypper_clean()).map_err(|e| PackageManagerError::Clean("zypper", e))?;
+        package_manager::remove_paths("zypper", ZYPPER_CACHE_PATHS)
+    }
+
+    fn list_installed(&self) -> Result<Vec<InstalledPackage>> {
+        dnf::list_rpm_packages("zypper")
+    }
+
+    fn package_files(&self, packages: &[String]) -> Result<Vec<PathBuf>> {
+        dnf::list_rpm_files("zypper", packages)
+    }
+
+    fn plan(&self, packages: &[String]) -> Result<Vec<PlannedPackage>> {
+        let output = command::run(zypper_plan(packages))
+            .map_err(|e| PackageManagerError::Plan("zypper", e))?;
+        Ok(parse_zypper_dry_run(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+}
+
+fn zypper() -> std::process::Command {
+    let mut cmd = std::process::Command::new("sudo");
+    cmd.arg("zypper").arg("--non-interactive");
+    cmd
+}
+
+fn zypper_refresh() -> std::process::Command {
+    let mut cmd = zypper();
+    cmd.arg("refresh");
+    cmd
+}
+
+fn zypper_install(packages: &[String]) -> std::process::Command {
+    let mut cmd = zypper();
+    cmd.arg("install").arg("--no-recommends").args(packages);
+    cmd
+}
+
+fn zypper_plan(packages: &[String]) -> std::process::Command {
+    let mut cmd = zypper();
+    cmd.arg("install")
+        .arg("--no-recommends")
+        .arg("--dry-run")
+        .args(packages);
+    cmd
+}
+
+/// Parse the names listed under `The following 2 NEW packages are going to be installed:`
+/// and the matching upgrade heading. zypper does not report versions or sizes per package
+fn parse_zypper_dry_run(output: &str) -> Vec<PlannedPackage> {
+    let mut planned = Vec::new();
+    let mut in_list = false;
+    for line in output.lines() {
+        if line.starts_with("The following") {
+            in_list =
+                line.ends_with("going to be installed:") || line.ends_with("going to be upgraded:");
+        } else if line.trim().is_empty() {
+            in_list = false;
+        } else if in_list {
+            planned.extend(line.split_whitespace().map(PlannedPackage::requested));
+        }
+    }
+    planned
+}
+
+fn zypper_remove(packages: &[String]) -> std::process::Command {
+    let mut cmd = zypper();
+    cmd.arg("remove").arg("--clean-deps").args(packages);
+    cmd
+}
+
+fn zypper_clean() -> std::process::Command {
+    let mut cmd = zypper();
+    cmd.arg("clean").arg("--all");
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn test_zypper() {
+        let packages = vec!["curl".to_string()];
+        let _ = install(&packages);
+    }
+
+    #[test]
+    #[serial]
+    fn test_zypper_install_command() {
+        let cmd = zypper_install(&["curl".to_string()]);
+        let description = command::describe(&cmd);
+        assert!(description.contains("zypper --non-interactive install --no-recommends curl"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_parse_zypper_dry_run() {
+        let output = "\
+Loading repository data...
+Reading installed packages...
+Resolving package dependencies...
+
+The following 2 NEW packages are going to be installed:
+  libtree1 tree
+
+The following package is going to be upgraded:
+  curl
+
+The following 2 packages are going to be REMOVED:
+  wget wget-lang
+
+3 packages to install.
+";
+        let names: Vec<String> = parse_zypper_dry_run(output)
+            .into_iter()
+            .map(|p| p.name)
+            .collect();
+        assert_eq!(names, vec!["libtree1", "tree", "curl"]);
+    }
+}
@@ -1,9 +1,13 @@
-mod installers;
-mod utils;
-
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use log::info;
+use picolayer::{installers, utils};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::time::Instant;
+use utils::output::OutputFormat;
 
 #[derive(Parser)]
 #[command(name = "picolayer")]
@@ -12,6 +16,51 @@ use log::info;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    #[command(flatten)]
+    layer: LayerArgs,
+
+    /// Print what would be installed without changing the system
+    #[arg(long, global = true, default_value = "false")]
+    dry_run: bool,
+
+    /// Reinstall even if the requested packages, release or feature are already installed
+    #[arg(long, global = true, default_value = "false")]
+    force: bool,
+
+    /// Format of the result printed when the command finishes
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+}
+
+#[derive(Args)]
+struct LayerArgs {
+    /// Report the bytes added per watched directory and the largest new files
+    #[arg(long, global = true, default_value = "false")]
+    layer_report: bool,
+
+    /// Fail when the layer grows by more than this size (e.g., 50M, 1G)
+    #[arg(long, global = true, value_parser = utils::snapshot::parse_size)]
+    max_layer_size: Option<u64>,
+
+    /// Directory to watch for layer size changes (default: /usr, /var, /etc, /opt, /tmp and $HOME)
+    #[arg(long, global = true)]
+    watch: Vec<String>,
+
+    /// Comma-separated sweep profiles removing files created during the run
+    #[arg(long, global = true, value_enum, value_delimiter = ',')]
+    sweep: Vec<utils::sweep::SweepProfile>,
+}
+
+#[derive(Args, Default)]
+struct LockArgs {
+    /// Lockfile recording the installed package versions
+    #[arg(long)]
+    lock: Option<PathBuf>,
+
+    /// Install exactly the versions recorded in the lockfile
+    #[arg(long, default_value = "false", requires = "lock")]
+    locked: bool,
 }
 
 #[derive(Subcommand)]
@@ -29,6 +78,13 @@ enum Commands {
         /// Force PPAs on non-Ubuntu systems
         #[arg(long, default_value = "false")]
         force_ppas_on_non_ubuntu: bool,
+
+        /// Signed apt repository as "KEYRING URI SUITE [COMPONENT...]" (can be repeated)
+        #[arg(long)]
+        repo: Vec<String>,
+
+        #[command(flatten)]
+        lock: LockArgs,
     },
 
     /// Install packages using apt
@@ -43,18 +99,57 @@ enum Commands {
         /// Force PPAs on non-Ubuntu systems
         #[arg(long, default_value = "false")]
         force_ppas_on_non_ubuntu: bool,
+
+        /// Signed apt repository as "KEYRING URI SUITE [COMPONENT...]" (can be repeated)
+        #[arg(long)]
+        repo: Vec<String>,
+
+        #[command(flatten)]
+        lock: LockArgs,
     },
 
     /// Install packages using aptitude
     Aptitude {
         /// Comma-separated list of packages to install
         packages: String,
+
+        #[command(flatten)]
+        lock: LockArgs,
     },
 
     /// Install packages using apk
     Apk {
         /// Comma-separated list of packages to install
         packages: String,
+
+        /// Extra repository, optionally tagged for pinning (e.g., "@testing https://...") (can be repeated)
+        #[arg(long)]
+        repository: Vec<String>,
+
+        /// Signing key for extra repositories as a URL or file path (can be repeated)
+        #[arg(long)]
+        key: Vec<String>,
+
+        #[command(flatten)]
+        lock: LockArgs,
+    },
+
+    /// Install packages using dnf (or microdnf/yum)
+    Dnf {
+        /// Comma-separated list of packages to install
+        packages: String,
+    },
+
+    /// Install packages using zypper
+    Zypper {
+        /// Comma-separated list of packages to install
+        packages: String,
+    },
+
+    /// Install packages using pacman
+    Pacman {
+        /// Comma-separated list of packages to install
+        packages: String,
     },
 
     /// Install packages using Homebrew
@@ -63,6 +158,42 @@ enum Commands {
         packages: String,
     },
 
+    /// Install packages using the native package manager of the detected distribution
+    Pkg {
+        /// Comma-separated list of packages to install
+        packages: String,
+
+        /// Package names across distributions (e.g., alpine:build-base=debian:build-essential)
+        #[arg(long)]
+        map: Vec<String>,
+    },
+
+    /// Install build dependencies, run a command, then purge the packages they added
+    Build {
+        /// Comma-separated list of build dependencies
+        #[arg(long)]
+        build_deps: String,
+
+        /// Package names across distributions (e.g., alpine:build-base=debian:build-essential)
+        #[arg(long)]
+        map: Vec<String>,
+
+        /// Working directory for the command
+        #[arg(long, default_value = ".")]
+        working_dir: String,
+
+        /// Command to run after installing the build dependencies
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+
+    /// Apply the installs declared in a manifest in one process
+    Apply {
+        /// Path to the manifest
+        #[arg(default_value = "picolayer.toml")]
+        manifest: PathBuf,
+    },
+
     /// Install a devcontainer feature
     #[command(name = "devcontainer-feature")]
     DevcontainerFeature {
@@ -91,7 +222,7 @@ enum Commands {
         /// Comma-separated list of binary names
         binary_names: String,
 
-        /// Version to install (default: latest)
+        /// Release tag or version range to install (e.g., v2.40.0, "^1.2", "~2.40", "<3")
         #[arg(long, default_value = "latest")]
         version: String,
 
@@ -114,6 +245,55 @@ enum Commands {
         /// GPG public key for signature verification (can be a URL, file path, or key content)
         #[arg(long)]
         gpg_key: Option<String>,
+
+        /// Regex extracting the version from release tags (e.g., "^cli-v(.+)$")
+        #[arg(long)]
+        tag_regex: Option<String>,
+
+        /// Consider prereleases and drafts when resolving latest or a version range
+        #[arg(long, default_value = "false")]
+        include_prereleases: bool,
+
+        /// File holding an API token (default: GITHUB_TOKEN/GH_TOKEN, GH_ENTERPRISE_TOKEN/GITHUB_ENTERPRISE_TOKEN with --api-url, GITEA_TOKEN/FORGEJO_TOKEN or GITLAB_TOKEN by --source)
+        #[arg(long)]
+        token_file: Option<String>,
+
+        /// Forge serving the releases
+        #[arg(long, value_enum, default_value_t = installers::gh_release::Forge::Github)]
+        source: installers::gh_release::Forge,
+
+        /// API of a self-hosted forge (e.g., https://ghe.example.com/api/v3 or https://codeberg.org)
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+
+    /// Install binary from a URL or local file
+    Url {
+        /// URL of the asset, with optional {os}, {arch} and {version} placeholders (e.g., https://releases.hashicorp.com/terraform/{version}/terraform_{version}_{os}_{arch}.zip, file:///srv/tool.tar.gz)
+        url: String,
+
+        /// Comma-separated list of binary names
+        binary_names: String,
+
+        /// Version substituted for {version}
+        #[arg(long)]
+        version: Option<String>,
+
+        /// Directory to install binaries
+        #[arg(long, default_value = "/usr/local/bin")]
+        install_dir: String,
+
+        /// Checksum text for verification (e.g., "sha256:5d3d3c60ffcf601f964bb4060a4234f9a96a3b09a7cdf67d1e61ae88efcd48f4")
+        #[arg(long)]
+        checksum_text: Option<String>,
+
+        /// GPG public key for signature verification (can be a URL, file path, or key content)
+        #[arg(long, requires = "signature_url")]
+        gpg_key: Option<String>,
+
+        /// URL of the detached signature, with the same placeholders as the asset URL
+        #[arg(long, requires = "gpg_key")]
+        signature_url: Option<String>,
     },
 
     /// Run a command using pkgx
@@ -133,55 +313,305 @@ enum Commands {
         #[arg(long)]
         env: Vec<String>,
     },
+
+    /// Reinstall gh-release binaries whose repository has a newer release
+    Upgrade {
+        /// Repository of one install to upgrade (default: all gh-release installs)
+        name: Option<String>,
+
+        /// Only list outdated installs, failing if there are any
+        #[arg(long, default_value = "false")]
+        check: bool,
+
+        /// File holding an API token (default: the variables of each install's forge)
+        #[arg(long)]
+        token_file: Option<String>,
+    },
+
+    /// List the recorded installs
+    List,
+
+    /// Show the receipt recorded for an install
+    Info {
+        /// Name of the install (e.g., cli/cli or a feature id)
+        name: String,
+    },
+
+    /// Remove the files an install wrote and forget its receipt
+    Uninstall {
+        /// Name of the install (e.g., cli/cli or a feature id)
+        name: String,
+    },
 }
 
 fn normalize_pkg_input(packages: String) -> Vec<String> {
     packages.split(',').map(|s| s.trim().to_string()).collect()
 }
 
+/// Parse key=value pairs, ignoring entries without a separator
+fn parse_key_values(pairs: Vec<String>) -> Option<HashMap<String, String>> {
+    if pairs.is_empty() {
+        return None;
+    }
+    Some(
+        pairs
+            .iter()
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    )
+}
+
+/// What a command produced: the plan on dry runs, otherwise what it installed
+struct Outcome {
+    command: &'static str,
+    dry_run: bool,
+    /// Printed with text output
+    text: Option<String>,
+    result: serde_json::Value,
+    /// Error returned after the result is printed
+    failure: Option<String>,
+}
+
+impl Outcome {
+    fn plan(command: &'static str, plan: impl Serialize + Display) -> Result<Self> {
+        Ok(Self {
+            command,
+            dry_run: true,
+            text: Some(plan.to_string()),
+            result: serde_json::to_value(plan)?,
+            failure: None,
+        })
+    }
+
+    /// Results that report `skipped` print the reason with text output
+    fn installed(command: &'static str, result: impl Serialize) -> Result<Self> {
+        let result = serde_json::to_value(result)?;
+        Ok(Self {
+            command,
+            dry_run: false,
+            text: result["skipped"]
+                .as_str()
+                .map(|reason| format!("{}\n", reason)),
+            result,
+            failure: None,
+        })
+    }
+
+    fn report(command: &'static str, result: impl Serialize + Display) -> Result<Self> {
+        Ok(Self {
+            text: Some(result.to_string()),
+            ..Self::installed(command, result)?
+        })
+    }
+}
+
+/// Install packages, or plan what would be installed on a dry run
+fn install_packages(
+    command: &'static str,
+    manager: &dyn installers::package_manager::PackageManager,
+    packages: &[String],
+    lock: &LockArgs,
+    dry_run: bool,
+    force: bool,
+) -> Result<Outcome> {
+    if !dry_run {
+        return Outcome::installed(command, install_with_lock(manager, packages, lock, force)?);
+    }
+
+    let plan = match &lock.lock {
+        Some(path) if lock.locked => {
+            let pinned = utils::lockfile::Lockfile::load(path)?.pin(packages)?;
+            installers::package_manager::plan(manager, &pinned)?
+        }
+        _ => installers::package_manager::plan(manager, packages)?,
+    };
+    Outcome::plan(command, plan)
+}
+
+/// Install packages pinned to a lockfile, or record the installed versions in one
+fn install_with_lock(
+    manager: &dyn installers::package_manager::PackageManager,
+    packages: &[String],
+    lock: &LockArgs,
+    force: bool,
+) -> Result<installers::package_manager::PackageInstall> {
+    let install = |packages: &[String]| {
+        if !force
+            && let Some(skipped) = installers::package_manager::already_installed(manager, packages)
+        {
+            return Ok(skipped);
+        }
+        installers::package_manager::install(manager, packages)
+    };
+
+    let Some(path) = &lock.lock else {
+        return install(packages);
+    };
+
+    if lock.locked {
+        let pinned = utils::lockfile::Lockfile::load(path)?.pin(packages)?;
+        return install(&pinned);
+    }
+
+    let installed = install(packages)?;
+    let lockfile = utils::lockfile::Lockfile::from_installed(packages, &manager.list_installed()?);
+    lockfile.save(path)?;
+    info!("Wrote lockfile: {}", path.display());
+    Ok(installed)
+}
+
 fn main() -> Result<()> {
     utils::logging::init_logging().context("Failed to initialize logging")?;
     info!("Starting picolayer");
     let cli = Cli::parse();
+    let started = Instant::now();
+    let layer = cli.layer;
+
+    if cli.dry_run {
+        let outcome = run(cli.command, true, cli.force, cli.output)?;
+        return print_outcome(outcome, cli.output, &layer, started, None);
+    }
+
+    // The sweep is removed even when the install fails, and the install's error wins
+    let sweep = utils::sweep::capture(&layer.sweep);
+    if !layer.layer_report && layer.max_layer_size.is_none() {
+        let outcome = run(cli.command, false, cli.force, cli.output);
+        let swept = sweep.remove();
+        let outcome = outcome?;
+        swept?;
+        return print_outcome(outcome, cli.output, &layer, started, None);
+    }
+
+    let roots = utils::snapshot::watched_roots(&layer.watch);
+    let before = utils::snapshot::Snapshot::capture(&roots);
+    let outcome = run(cli.command, false, cli.force, cli.output);
+    let swept = sweep.remove();
+    let outcome = outcome?;
+    swept?;
+    let diff = before.diff(&utils::snapshot::Snapshot::capture(&roots));
+
+    print_outcome(outcome, cli.output, &layer, started, Some(&diff))?;
+    if let Some(max_layer_size) = layer.max_layer_size {
+        diff.check_budget(max_layer_size)?;
+    }
 
-    match cli.command {
+    Ok(())
+}
+
+/// Print the plan or layer report as text, or the whole result as JSON
+fn print_outcome(
+    outcome: Outcome,
+    output: OutputFormat,
+    layer: &LayerArgs,
+    started: Instant,
+    diff: Option<&utils::snapshot::LayerDiff>,
+) -> Result<()> {
+    let report = diff.filter(|_| layer.layer_report);
+    let failure = outcome.failure;
+    match output {
+        OutputFormat::Text => {
+            if let Some(text) = outcome.text {
+                print!("{}", text);
+            }
+            if let Some(diff) = report {
+                print!("{}", diff);
+            }
+        }
+        OutputFormat::Json => {
+            let report = utils::output::Report {
+                command: outcome.command,
+                dry_run: outcome.dry_run,
+                elapsed_secs: started.elapsed().as_secs_f64(),
+                bytes_added: diff.map(|diff| diff.total()),
+                layer: report,
+                result: outcome.result,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+    match failure {
+        Some(failure) => Err(anyhow::anyhow!(failure)),
+        None => Ok(()),
+    }
+}
+
+/// Run a command; with JSON `output`, commands it runs for the user write their stdout to stderr
+fn run(command: Commands, dry_run: bool, force: bool, output: OutputFormat) -> Result<Outcome> {
+    let outcome = match command {
         Commands::AptGet {
             packages,
             ppas,
             force_ppas_on_non_ubuntu,
+            repo,
+            lock,
         } => {
             let pkg_list: Vec<String> = normalize_pkg_input(packages);
             let ppa_list: Option<Vec<String>> = ppas.map(normalize_pkg_input);
+            let repositories = repo
+                .iter()
+                .map(|r| r.parse())
+                .collect::<Result<Vec<installers::apt_repository::AptRepository>>>()?;
             let _ = utils::analytics::track_command(
                 "apt-get",
                 Some(serde_json::json!({
                     "package_count": pkg_list.len(),
                     "has_ppas": ppa_list.is_some(),
+                    "repository_count": repositories.len(),
                 })),
             );
 
-            installers::apt_get::install(&pkg_list, ppa_list.as_deref(), force_ppas_on_non_ubuntu)?;
+            install_packages(
+                "apt-get",
+                &installers::apt_get::AptGet {
+                    ppas: ppa_list.unwrap_or_default(),
+                    force_ppas_on_non_ubuntu,
+                    repositories,
+                },
+                &pkg_list,
+                &lock,
+                dry_run,
+                force,
+            )?
         }
 
         Commands::Apt {
             packages,
             ppas,
             force_ppas_on_non_ubuntu,
+            repo,
+            lock,
         } => {
             let pkg_list: Vec<String> = normalize_pkg_input(packages);
             let ppa_list: Option<Vec<String>> = ppas.map(normalize_pkg_input);
+            let repositories = repo
+                .iter()
+                .map(|r| r.parse())
+                .collect::<Result<Vec<installers::apt_repository::AptRepository>>>()?;
             let _ = utils::analytics::track_command(
                 "apt",
                 Some(serde_json::json!({
                     "package_count": pkg_list.len(),
                     "has_ppas": ppa_list.is_some(),
+                    "repository_count": repositories.len(),
                 })),
             );
 
-            installers::apt::install(&pkg_list, ppa_list.as_deref(), force_ppas_on_non_ubuntu)?;
+            install_packages(
+                "apt",
+                &installers::apt::Apt {
+                    ppas: ppa_list.unwrap_or_default(),
+                    force_ppas_on_non_ubuntu,
+                    repositories,
+                },
+                &pkg_list,
+                &lock,
+                dry_run,
+                force,
+            )?
         }
 
-        Commands::Aptitude { packages } => {
+        Commands::Aptitude { packages, lock } => {
             let pkg_list: Vec<String> = normalize_pkg_input(packages);
             let _ = utils::analytics::track_command(
                 "aptitude",
@@ -190,19 +620,99 @@ fn main() -> Result<()> {
                 })),
             );
 
-            installers::aptitude::install(&pkg_list)?;
+            install_packages(
+                "aptitude",
+                &installers::aptitude::Aptitude,
+                &pkg_list,
+                &lock,
+                dry_run,
+                force,
+            )?
         }
 
-        Commands::Apk { packages } => {
+        Commands::Apk {
+            packages,
+            repository,
+            key,
+            lock,
+        } => {
             let pkg_list: Vec<String> = normalize_pkg_input(packages);
             let _ = utils::analytics::track_command(
                 "apk",
+                Some(serde_json::json!({
+                    "package_count": pkg_list.len(),
+                    "repository_count": repository.len(),
+                })),
+            );
+
+            install_packages(
+                "apk",
+                &installers::apk::Apk {
+                    repositories: repository,
+                    keys: key,
+                },
+                &pkg_list,
+                &lock,
+                dry_run,
+                force,
+            )?
+        }
+
+        Commands::Dnf { packages } => {
+            let pkg_list: Vec<String> = normalize_pkg_input(packages);
+            let _ = utils::analytics::track_command(
+                "dnf",
+                Some(serde_json::json!({
+                    "package_count": pkg_list.len(),
+                })),
+            );
+
+            install_packages(
+                "dnf",
+                &installers::dnf::Dnf,
+                &pkg_list,
+                &LockArgs::default(),
+                dry_run,
+                force,
+            )?
+        }
+
+        Commands::Zypper { packages } => {
+            let pkg_list: Vec<String> = normalize_pkg_input(packages);
+            let _ = utils::analytics::track_command(
+                "zypper",
                 Some(serde_json::json!({
                     "package_count": pkg_list.len(),
                 })),
             );
 
-            installers::apk::install(&pkg_list)?;
+            install_packages(
+                "zypper",
+                &installers::zypper::Zypper,
+                &pkg_list,
+                &LockArgs::default(),
+                dry_run,
+                force,
+            )?
+        }
+
+        Commands::Pacman { packages } => {
+            let pkg_list: Vec<String> = normalize_pkg_input(packages);
+            let _ = utils::analytics::track_command(
+                "pacman",
+                Some(serde_json::json!({
+                    "package_count": pkg_list.len(),
+                })),
+            );
+
+            install_packages(
+                "pacman",
+                &installers::pacman::Pacman,
+                &pkg_list,
+                &LockArgs::default(),
+                dry_run,
+                force,
+            )?
         }
 
         Commands::Brew { packages } => {
@@ -214,7 +724,81 @@ fn main() -> Result<()> {
                 })),
             );
 
-            installers::brew::install(&pkg_list)?;
+            install_packages(
+                "brew",
+                &installers::brew::Brew,
+                &pkg_list,
+                &LockArgs::default(),
+                dry_run,
+                force,
+            )?
+        }
+
+        Commands::Pkg { packages, map } => {
+            let pkg_list: Vec<String> = normalize_pkg_input(packages);
+            let mappings = map
+                .iter()
+                .map(|m| m.parse())
+                .collect::<Result<Vec<installers::pkg::PackageMapping>>>()?;
+            let _ = utils::analytics::track_command(
+                "pkg",
+                Some(serde_json::json!({
+                    "package_count": pkg_list.len(),
+                    "mapping_count": mappings.len(),
+                })),
+            );
+
+            if dry_run {
+                Outcome::plan("pkg", installers::pkg::plan(&pkg_list, &mappings)?)?
+            } else {
+                Outcome::installed("pkg", installers::pkg::install(&pkg_list, &mappings)?)?
+            }
+        }
+
+        Commands::Apply { manifest } => {
+            let _ = utils::analytics::track_command("apply", None);
+
+            if dry_run {
+                Outcome::plan("apply", installers::manifest::plan(&manifest)?)?
+            } else {
+                Outcome::installed(
+                    "apply",
+                    installers::manifest::apply(&manifest, force, output)?,
+                )?
+            }
+        }
+
+        Commands::Build {
+            build_deps,
+            map,
+            working_dir,
+            command,
+        } => {
+            let deps_list: Vec<String> = normalize_pkg_input(build_deps);
+            let mappings = map
+                .iter()
+                .map(|m| m.parse())
+                .collect::<Result<Vec<installers::pkg::PackageMapping>>>()?;
+            let _ = utils::analytics::track_command(
+                "build",
+                Some(serde_json::json!({
+                    "build_dep_count": deps_list.len(),
+                    "mapping_count": mappings.len(),
+                })),
+            );
+
+            let config = installers::build::BuildConfig {
+                build_deps: &deps_list,
+                mappings: &mappings,
+                command: &command,
+                working_dir: &working_dir,
+                output,
+            };
+            if dry_run {
+                Outcome::plan("build", installers::build::plan(&config)?)?
+            } else {
+                Outcome::installed("build", installers::build::run(&config)?)?
+            }
         }
 
         Commands::DevcontainerFeature {
@@ -233,36 +817,31 @@ fn main() -> Result<()> {
                 })),
             );
 
-            let options = if !option.is_empty() {
-                let mut opts = std::collections::HashMap::new();
-                for opt in option {
-                    if let Some((key, value)) = opt.split_once('=') {
-                        opts.insert(key.to_string(), value.to_string());
-                    }
-                }
-                Some(opts)
-            } else {
-                None
-            };
+            let options = parse_key_values(option);
+            let envs = parse_key_values(env);
 
-            let envs = if !env.is_empty() {
-                let mut env_map = std::collections::HashMap::new();
-                for e in env {
-                    if let Some((key, value)) = e.split_once('=') {
-                        env_map.insert(key.to_string(), value.to_string());
-                    }
-                }
-                Some(env_map)
+            if dry_run {
+                Outcome::plan(
+                    "devcontainer-feature",
+                    installers::devcontainer_feature::plan(
+                        &feature,
+                        options,
+                        remote_user.as_deref(),
+                        envs,
+                    )?,
+                )?
             } else {
-                None
-            };
-
-            installers::devcontainer_feature::install(
-                &feature,
-                options,
-                remote_user.as_deref(),
-                envs,
-            )?;
+                Outcome::installed(
+                    "devcontainer-feature",
+                    installers::devcontainer_feature::install(
+                        &feature,
+                        options,
+                        remote_user.as_deref(),
+                        envs,
+                        force,
+                    )?,
+                )?
+            }
         }
 
         Commands::GhRelease {
@@ -274,6 +853,11 @@ fn main() -> Result<()> {
             verify_checksum,
             checksum_text,
             gpg_key,
+            tag_regex,
+            include_prereleases,
+            token_file,
+            source,
+            api_url,
         } => {
             let binary_list: Vec<String> = binary_names
                 .split(',')
@@ -289,10 +873,11 @@ fn main() -> Result<()> {
                     "has_filter": filter.is_some(),
                     "verify_checksum": verify_checksum,
                     "has_gpg_key": gpg_key.is_some(),
+                    "source": source.to_string(),
                 })),
             );
 
-            installers::gh_release::install(&installers::gh_release::GhReleaseConfig {
+            let config = installers::gh_release::GhReleaseConfig {
                 repo: &repo,
                 binary_names: &binary_list,
                 version: &version,
@@ -301,7 +886,59 @@ fn main() -> Result<()> {
                 verify_checksum,
                 checksum_text: checksum_text.as_deref(),
                 gpg_key: gpg_key.as_deref(),
-            })?;
+                tag_regex: tag_regex.as_deref(),
+                include_prereleases,
+                token_file: token_file.as_deref(),
+                source,
+                api_url: api_url.as_deref(),
+                force,
+            };
+            if dry_run {
+                Outcome::plan("gh-release", installers::gh_release::plan(&config)?)?
+            } else {
+                Outcome::installed("gh-release", installers::gh_release::install(&config)?)?
+            }
+        }
+
+        Commands::Url {
+            url,
+            binary_names,
+            version,
+            install_dir,
+            checksum_text,
+            gpg_key,
+            signature_url,
+        } => {
+            let binary_list: Vec<String> = binary_names
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .collect();
+
+            let _ = utils::analytics::track_command(
+                "url",
+                Some(serde_json::json!({
+                    "binary_count": binary_list.len(),
+                    "has_version": version.is_some(),
+                    "has_checksum": checksum_text.is_some(),
+                    "has_gpg_key": gpg_key.is_some(),
+                })),
+            );
+
+            let config = installers::url::UrlConfig {
+                url: &url,
+                binary_names: &binary_list,
+                version: version.as_deref(),
+                install_dir: &install_dir,
+                checksum_text: checksum_text.as_deref(),
+                gpg_key: gpg_key.as_deref(),
+                signature_url: signature_url.as_deref(),
+                force,
+            };
+            if dry_run {
+                Outcome::plan("url", installers::url::plan(&config)?)?
+            } else {
+                Outcome::installed("url", installers::url::install(&config)?)?
+            }
         }
 
         Commands::X {
@@ -319,16 +956,89 @@ fn main() -> Result<()> {
                 })),
             );
 
-            installers::x::execute(&installers::x::RunConfig {
+            let config = installers::x::RunConfig {
                 tool: &tool,
                 args,
                 working_dir: &working_dir,
                 env_vars: env,
-            })?;
+                output,
+            };
+            if dry_run {
+                Outcome::plan("x", installers::x::plan(&config)?)?
+            } else {
+                Outcome::installed("x", installers::x::execute(&config)?)?
+            }
         }
-    }
 
-    Ok(())
+        Commands::Upgrade {
+            name,
+            check,
+            token_file,
+        } => {
+            let _ = utils::analytics::track_command(
+                "upgrade",
+                Some(serde_json::json!({
+                    "has_name": name.is_some(),
+                    "check": check,
+                })),
+            );
+
+            let upgrades = installers::upgrade::upgrade(
+                name.as_deref(),
+                check || dry_run,
+                force,
+                token_file.as_deref(),
+            )?;
+            let failure = (check && !upgrades.outdated.is_empty()).then(|| {
+                format!(
+                    "{} gh-release installs are outdated",
+                    upgrades.outdated.len()
+                )
+            });
+            let outcome = if dry_run {
+                Outcome::plan("upgrade", upgrades)?
+            } else {
+                Outcome::report("upgrade", upgrades)?
+            };
+            Outcome { failure, ..outcome }
+        }
+
+        Commands::List => {
+            let _ = utils::analytics::track_command("list", None);
+            Outcome::report("list", utils::receipts::list()?)?
+        }
+
+        Commands::Info { name } => {
+            let _ = utils::analytics::track_command("info", None);
+            Outcome::report("info", utils::receipts::find(&name)?)?
+        }
+
+        Commands::Uninstall { name } => {
+            let _ = utils::analytics::track_command("uninstall", None);
+
+            if dry_run {
+                Outcome::plan("uninstall", utils::receipts::find(&name)?)?
+            } else {
+                let packages = installers::pkg::remove_recorded(&utils::receipts::find(&name)?)?;
+                let uninstalled = utils::receipts::uninstall(&name)?;
+                let mut text = format!(
+                    "Uninstalled {} ({} files, {} packages removed)\n",
+                    uninstalled.receipt.name,
+                    uninstalled.removed(),
+                    packages.len()
+                );
+                for path in &uninstalled.kept {
+                    text.push_str(&format!("Kept {}\n", path.display()));
+                }
+                Outcome {
+                    text: Some(text),
+                    ..Outcome::installed("uninstall", uninstalled)?
+                }
+            }
+        }
+    };
+
+    Ok(outcome)
 }
 
 #[cfg(test)]
@@ -0,0 +1,112 @@
+use log::debug;
+use std::process::{Command, ExitStatus, Output};
+
+/// Number of trailing output lines kept in error messages
+const OUTPUT_TAIL_LINES: usize = 40;
+
+/// A subprocess that could not be started or exited unsuccessfully
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("Failed to run `{command}`")]
+    Spawn {
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("`{command}` exited with {status}{}", format_output(stdout, stderr))]
+    Status {
+        command: String,
+        status: ExitStatus,
+        stdout: String,
+        stderr: String,
+    },
+}
+
+/// Run a command to completion and fail if it exits with a non-zero status
+pub fn run(mut cmd: Command) -> Result<Output, CommandError> {
+    let command = describe(&cmd);
+    debug!("Running: {}", command);
+
+    let output = cmd.output().map_err(|source| CommandError::Spawn {
+        command: command.clone(),
+        source,
+    })?;
+    debug!("Output of `{}`: {:?}", command, output);
+
+    if output.status.success() {
+        Ok(output)
+    } else {
+        Err(CommandError::Status {
+            command,
+            status: output.status,
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// Render a command line for logs and error messages
+pub fn describe(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|s| s.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn format_output(stdout: &str, stderr: &str) -> String {
+    let mut message = String::new();
+    for (label, content) in [("stdout", stdout), ("stderr", stderr)] {
+        let content = tail(content.trim(), OUTPUT_TAIL_LINES);
+        if !content.is_empty() {
+            message.push_str(&format!("\n--- {} ---\n{}", label, content));
+        }
+    }
+    message
+}
+
+fn tail(content: &str, lines: usize) -> &str {
+    match content.rmatch_indices('\n').nth(lines.saturating_sub(1)) {
+        Some((idx, _)) => &content[idx + 1..],
+        None => content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_success() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo hello");
+        let output = run(cmd).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "hello");
+    }
+
+    #[test]
+    fn test_run_non_zero_exit_includes_output() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("echo out; echo err >&2; exit 3");
+        let err = run(cmd).unwrap_err();
+        let message = err.to_string();
+        assert!(matches!(err, CommandError::Status { .. }));
+        assert!(message.contains("sh -c"));
+        assert!(message.contains("out"));
+        assert!(message.contains("err"));
+    }
+
+    #[test]
+    fn test_run_missing_program() {
+        let err = run(Command::new("picolayer-nonexistent-program")).unwrap_err();
+        assert!(matches!(err, CommandError::Spawn { .. }));
+    }
+
+    #[test]
+    fn test_tail() {
+        assert_eq!(tail("a\nb\nc", 2), "b\nc");
+        assert_eq!(tail("a\nb\nc", 5), "a\nb\nc");
+        assert_eq!(tail("", 2), "");
+    }
+}
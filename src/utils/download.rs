@@ -0,0 +1,419 @@
+use crate::utils::github::{self, Token};
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, warn};
+use reqwest::StatusCode;
+use reqwest::Url;
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_RANGE, ETAG, HeaderMap, IF_RANGE, LAST_MODIFIED, RANGE};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Attempts made before a download is given up
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, doubled after each further attempt
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A file downloaded to disk along with the SHA-256 of its contents
+#[derive(Debug)]
+pub struct Download {
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Streams downloads to disk, retrying server errors and resuming interrupted transfers
+pub struct Downloader<'a> {
+    client: &'a Client,
+    token: Option<&'a Token>,
+    backoff: Duration,
+}
+
+/// Why a download attempt stopped
+enum Failure {
+    /// Server errors and dropped connections, retried after a backoff
+    Retry(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+/// Bytes received so far, kept across attempts so a retry can resume
+struct Partial {
+    file: File,
+    hasher: Sha256,
+    written: u64,
+    /// ETag or Last-Modified of the response the bytes came from, sent as `If-Range`
+    /// so that a changed file is sent in full instead of being appended to
+    validator: Option<String>,
+    bar: ProgressBar,
+}
+
+/// File name to save a download from `url` under: the last path segment, or `asset`
+/// when it is empty or could step outside the download directory
+pub fn file_name(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .map(String::from)
+        })
+        .filter(|name| {
+            !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+        })
+        .unwrap_or_else(|| "asset".to_string())
+}
+
+impl<'a> Downloader<'a> {
+    pub fn new(client: &'a Client) -> Self {
+        Self {
+            client,
+            token: None,
+            backoff: INITIAL_BACKOFF,
+        }
+    }
+
+    /// Send the token when the URL is on GitHub
+    pub fn github_token(mut self, token: Option<&'a Token>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Delay before the first retry
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Download `url` to `dest`, hashing the contents while writing
+    pub fn download(&self, url: &str, dest: &Path) -> Result<Download> {
+        let file =
+            File::create(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+        let bar = ProgressBar::no_length();
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{msg:.dim} ❲{wide_bar:.cyan/blue}❳ {percent}% {bytes_per_sec:.dim} {bytes:.dim}",
+            )?
+            .progress_chars("██░"),
+        );
+        if let Some(name) = dest.file_name() {
+            bar.set_message(name.to_string_lossy().into_owned());
+        }
+        let mut partial = Partial {
+            file,
+            hasher: Sha256::new(),
+            written: 0,
+            validator: None,
+            bar,
+        };
+
+        let mut attempt = 1;
+        loop {
+            match self.attempt(url, &mut partial) {
+                Ok(()) => break,
+                Err(Failure::Fatal(e)) => {
+                    partial.bar.abandon();
+                    return Err(e);
+                }
+                Err(Failure::Retry(e)) if attempt < MAX_ATTEMPTS => {
+                    let delay = self.backoff * 2u32.pow(attempt - 1);
+                    warn!(
+                        "Download of {} failed ({:#}), retrying in {:?}",
+                        url, e, delay
+                    );
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                Err(Failure::Retry(e)) => {
+                    partial.bar.abandon();
+                    return Err(e.context(format!(
+                        "Failed to download {} after {} attempts",
+                        url, MAX_ATTEMPTS
+                    )));
+                }
+            }
+        }
+
+        partial.bar.finish_and_clear();
+        partial.file.flush()?;
+        Ok(Download {
+            path: dest.to_path_buf(),
+            sha256: hex::encode(partial.hasher.finalize()),
+            size: partial.written,
+        })
+    }
+
+    /// Request the bytes not received yet and append them to the partial download
+    fn attempt(&self, url: &str, partial: &mut Partial) -> Result<(), Failure> {
+        let request = self.client.get(url).header("User-Agent", "picolayer");
+        let mut request = github::authorize(request, url, self.token);
+        if partial.written > 0 {
+            debug!("Resuming {} from byte {}", url, partial.written);
+            request = request.header(RANGE, format!("bytes={}-", partial.written));
+            if let Some(validator) = &partial.validator {
+                request = request.header(IF_RANGE, validator);
+            }
+        }
+
+        let mut response = request.send().map_err(|e| {
+            let invalid_request = e.is_builder();
+            let error = anyhow::Error::new(e).context("Failed to connect");
+            if invalid_request {
+                Failure::Fatal(error)
+            } else {
+                Failure::Retry(error)
+            }
+        })?;
+
+        let status = response.status();
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(Failure::Retry(anyhow::anyhow!(
+                "Server responded with {}",
+                status
+            )));
+        }
+        if !status.is_success() {
+            return Err(Failure::Fatal(anyhow::anyhow!(
+                "Failed to download {}: {}",
+                url,
+                status
+            )));
+        }
+        if status == StatusCode::PARTIAL_CONTENT {
+            let start = range_start(response.headers());
+            if start != Some(partial.written) {
+                partial.restart().map_err(|e| Failure::Fatal(e.into()))?;
+                return Err(Failure::Retry(anyhow::anyhow!(
+                    "Server resumed at byte {:?} instead of {}, restarting the download",
+                    start,
+                    partial.written
+                )));
+            }
+        } else {
+            if partial.written > 0 {
+                debug!("Server sent the whole file, restarting the download");
+                partial.restart().map_err(|e| Failure::Fatal(e.into()))?;
+            }
+            partial.validator = validator(response.headers());
+        }
+
+        let expected = response.content_length().map(|len| partial.written + len);
+        if let Some(expected) = expected {
+            partial.bar.set_length(expected);
+        }
+
+        let mut buf = vec![0; CHUNK_SIZE];
+        loop {
+            let read = match response.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    return Err(Failure::Retry(
+                        anyhow::Error::new(e).context("Download interrupted"),
+                    ));
+                }
+            };
+            partial
+                .append(&buf[..read])
+                .map_err(|e| Failure::Fatal(e.into()))?;
+        }
+
+        match expected {
+            Some(expected) if partial.written < expected => Err(Failure::Retry(anyhow::anyhow!(
+                "Download ended after {} of {} bytes",
+                partial.written,
+                expected
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// First byte of a `Content-Range: bytes START-END/TOTAL` header
+fn range_start(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split_once('-')?
+        .0
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Strong ETag, or else Last-Modified, identifying the version of a file; weak ETags
+/// cannot be used with `If-Range`
+fn validator(headers: &HeaderMap) -> Option<String> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(LAST_MODIFIED))
+        .map(String::from)
+}
+
+impl Partial {
+    fn append(&mut self, chunk: &[u8]) -> io::Result<()> {
+        self.file.write_all(chunk)?;
+        self.hasher.update(chunk);
+        self.written += chunk.len() as u64;
+        self.bar.set_position(self.written);
+        Ok(())
+    }
+
+    fn restart(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        self.hasher = Sha256::new();
+        self.written = 0;
+        self.validator = None;
+        self.bar.set_position(0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+
+    /// Serve one canned response per connection, returning the requests received
+    fn serve(responses: Vec<Vec<u8>>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/asset", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = io::BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    request.push_str(&line.to_lowercase());
+                }
+                requests.push(request);
+                stream.write_all(&response).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn test_download_retries_and_resumes() {
+        let body = b"0123456789";
+        let (url, server) = serve(vec![
+            b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_vec(),
+            [
+                &b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nETag: \"v1\"\r\nConnection: close\r\n\r\n"[..],
+                &body[..4],
+            ]
+            .concat(),
+            [
+                &b"HTTP/1.1 206 Partial Content\r\nContent-Length: 6\r\nContent-Range: bytes 4-9/10\r\nConnection: close\r\n\r\n"[..],
+                &body[4..],
+            ]
+            .concat(),
+        ]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("asset");
+        let client = Client::new();
+        let download = Downloader::new(&client)
+            .backoff(Duration::from_millis(1))
+            .download(&url, &dest)
+            .unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert_eq!(download.size, 10);
+        assert_eq!(download.sha256, hex::encode(Sha256::digest(body)));
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(!requests[1].contains("range:"));
+        assert!(requests[2].contains("range: bytes=4-"));
+        assert!(requests[2].contains("if-range: \"v1\""));
+    }
+
+    #[test]
+    fn test_download_restarts_on_mismatched_range() {
+        let body = b"0123456789";
+        let (url, server) = serve(vec![
+            [
+                &b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n"[..],
+                &body[..4],
+            ]
+            .concat(),
+            [
+                &b"HTTP/1.1 206 Partial Content\r\nContent-Length: 8\r\nContent-Range: bytes 2-9/10\r\nConnection: close\r\n\r\n"[..],
+                &body[2..],
+            ]
+            .concat(),
+            [
+                &b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\nConnection: close\r\n\r\n"[..],
+                &body[..],
+            ]
+            .concat(),
+        ]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("asset");
+        let client = Client::new();
+        let download = Downloader::new(&client)
+            .backoff(Duration::from_millis(1))
+            .download(&url, &dest)
+            .unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+        assert_eq!(download.sha256, hex::encode(Sha256::digest(body)));
+
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("range: bytes=4-"));
+        assert!(!requests[2].contains("range:"));
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(
+            file_name("https://gitlab.com/group/tool/-/releases/v1/downloads/tool.tar.gz"),
+            "tool.tar.gz"
+        );
+        assert_eq!(
+            file_name("https://example.com/a/Linux%2Famd64"),
+            "Linux%2Famd64"
+        );
+        assert_eq!(file_name("https://example.com/a/../../x"), "x");
+        assert_eq!(file_name("https://example.com/dir/"), "asset");
+        assert_eq!(file_name("https://example.com"), "asset");
+        assert_eq!(file_name("not a url"), "asset");
+    }
+
+    #[test]
+    fn test_download_fails_on_client_error() {
+        let (url, server) = serve(vec![
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+        ]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let client = Client::new();
+        let result = Downloader::new(&client)
+            .backoff(Duration::from_millis(1))
+            .download(&url, &dir.path().join("asset"));
+
+        assert!(format!("{:#}", result.unwrap_err()).contains("404"));
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,257 @@
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use reqwest::StatusCode;
+use reqwest::Url;
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::{AUTHORIZATION, HeaderMap, RETRY_AFTER};
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Environment variables checked for a GitHub token, in order
+pub const TOKEN_VARS: &[&str] = &["GITHUB_TOKEN", "GH_TOKEN"];
+
+/// Environment variables checked for a GitHub Enterprise token, in order
+pub const ENTERPRISE_TOKEN_VARS: &[&str] = &["GH_ENTERPRISE_TOKEN", "GITHUB_ENTERPRISE_TOKEN"];
+
+/// Hosts that receive the token; release asset redirects to other hosts never do
+const GITHUB_HOSTS: [&str; 2] = ["api.github.com", "github.com"];
+
+/// Longest rate-limit reset waited for before giving up
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Requests retried after waiting out a rate limit
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// A GitHub token, kept out of logs and debug output
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token {
+    value: String,
+    /// Only host the token is sent to, instead of the GitHub hosts
+    host: Option<String>,
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Token(***)")
+    }
+}
+
+impl Token {
+    /// Read the token from `token_file` if given, otherwise from the first of `vars` set,
+    /// e.g. `TOKEN_VARS` for GitHub
+    pub fn resolve(token_file: Option<&str>, vars: &[&str]) -> Result<Option<Self>> {
+        if let Some(path) = token_file {
+            let content = std::fs::read_to_string(Path::new(path))
+                .with_context(|| format!("Failed to read token file: {}", path))?;
+            return Self::parse(&content)
+                .map(Some)
+                .with_context(|| format!("Token file is empty: {}", path));
+        }
+
+        Ok(vars
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find_map(|value| Self::parse(&value)))
+    }
+
+    /// Send the token to `host` only, for a self-hosted forge, instead of GitHub
+    pub fn for_host(self, host: &str) -> Self {
+        Self {
+            host: Some(host.to_string()),
+            ..self
+        }
+    }
+
+    /// Whether the token may be sent with a request to `url`, which must use https
+    fn allows(&self, url: &str) -> bool {
+        match &self.host {
+            Some(host) => Url::parse(url)
+                .is_ok_and(|url| url.scheme() == "https" && url.host_str() == Some(host)),
+            None => is_github_url(url),
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        (!value.is_empty()).then(|| Self {
+            value: value.to_string(),
+            host: None,
+        })
+    }
+}
+
+/// Whether `url` points at GitHub itself rather than a third-party host
+pub fn is_github_url(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| {
+        url.scheme() == "https"
+            && url
+                .host_str()
+                .is_some_and(|host| GITHUB_HOSTS.contains(&host))
+    })
+}
+
+/// Attach the token to requests for GitHub hosts, or the host it was scoped to.
+/// Redirects to other hosts, such as the storage serving release assets, drop the header
+pub fn authorize(request: RequestBuilder, url: &str, token: Option<&Token>) -> RequestBuilder {
+    match token {
+        Some(token) if token.allows(url) => {
+            request.header(AUTHORIZATION, format!("Bearer {}", token.value))
+        }
+        _ => request,
+    }
+}
+
+/// Send a GitHub API request, waiting out short rate limits and failing with
+/// the reset time when the limit lasts longer
+pub fn send(build: impl Fn() -> RequestBuilder, authenticated: bool) -> Result<Response> {
+    let mut retries = 0;
+    loop {
+        let response = build().send()?;
+        let Some(wait) = rate_limit_wait(response.status(), response.headers(), SystemTime::now())
+        else {
+            return Ok(response);
+        };
+
+        if wait > MAX_RATE_LIMIT_WAIT || retries == MAX_RATE_LIMIT_RETRIES {
+            let hint = if authenticated {
+                ""
+            } else {
+                "; set GITHUB_TOKEN or GH_TOKEN, or pass --token-file, to raise the limit"
+            };
+            anyhow::bail!(
+                "GitHub API rate limit exceeded, resets in {}{}",
+                format_wait(wait),
+                hint
+            );
+        }
+
+        warn!(
+            "GitHub API rate limit exceeded, retrying in {}",
+            format_wait(wait)
+        );
+        std::thread::sleep(wait);
+        retries += 1;
+    }
+}
+
+/// Time until a rate-limited request may be retried, or `None` if it was not rate limited
+fn rate_limit_wait(status: StatusCode, headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+    };
+
+    // Secondary rate limits send Retry-After; primary ones exhaust the remaining count
+    if let Some(seconds) = header(RETRY_AFTER.as_str()) {
+        debug!("Retry-After: {}s", seconds);
+        return Some(Duration::from_secs(seconds));
+    }
+    if header("x-ratelimit-remaining") != Some(0) {
+        return None;
+    }
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let reset = header("x-ratelimit-reset").map_or(MAX_RATE_LIMIT_WAIT, |reset| {
+        Duration::from_secs(reset).saturating_sub(now)
+    });
+    Some(reset)
+}
+
+fn format_wait(wait: Duration) -> String {
+    let seconds = wait.as_secs();
+    if seconds >= 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_github_url() {
+        assert!(is_github_url("https://api.github.com/repos/cli/cli"));
+        assert!(is_github_url(
+            "https://github.com/cli/cli/releases/download/v2.0.0/gh.tar.gz"
+        ));
+        assert!(!is_github_url(
+            "https://objects.githubusercontent.com/github-production-release-asset"
+        ));
+        assert!(!is_github_url("https://github.com.example.com/cli/cli"));
+        assert!(!is_github_url("http://api.github.com/repos/cli/cli"));
+        assert!(!is_github_url("not a url"));
+    }
+
+    #[test]
+    fn test_token_resolve_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        std::fs::write(&path, "ghp_secret\n").unwrap();
+
+        let token = Token::resolve(path.to_str(), TOKEN_VARS).unwrap().unwrap();
+        assert_eq!(token.value, "ghp_secret");
+        assert_eq!(format!("{:?}", token), "Token(***)");
+
+        std::fs::write(&path, "\n").unwrap();
+        assert!(Token::resolve(path.to_str(), TOKEN_VARS).is_err());
+        assert!(Token::resolve(dir.path().join("missing").to_str(), TOKEN_VARS).is_err());
+    }
+
+    #[test]
+    fn test_token_for_host() {
+        let token = Token::parse("secret").unwrap();
+        assert!(token.allows("https://github.com/cli/cli/releases/download/v1/gh"));
+        assert!(!token.allows("https://git.example.com/api/v1/repos/a/b"));
+
+        let token = token.for_host("git.example.com");
+        assert!(token.allows("https://git.example.com/api/v1/repos/a/b"));
+        assert!(!token.allows("https://api.github.com/repos/a/b"));
+        assert!(!token.allows("https://cdn.example.com/asset"));
+        assert!(!token.allows("http://git.example.com/api/v1/repos/a/b"));
+    }
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000);
+        let headers = |pairs: &[(&'static str, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, value.parse().unwrap());
+            }
+            headers
+        };
+
+        let exhausted = headers(&[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", "1090"),
+        ]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::FORBIDDEN, &exhausted, now),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(rate_limit_wait(StatusCode::OK, &exhausted, now), None);
+
+        let retry_after = headers(&[("retry-after", "5")]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::TOO_MANY_REQUESTS, &retry_after, now),
+            Some(Duration::from_secs(5))
+        );
+
+        // A 403 with requests left is a permissions error, not a rate limit
+        let remaining = headers(&[("x-ratelimit-remaining", "42")]);
+        assert_eq!(
+            rate_limit_wait(StatusCode::FORBIDDEN, &remaining, now),
+            None
+        );
+
+        assert_eq!(format_wait(Duration::from_secs(90)), "1m 30s");
+        assert_eq!(format_wait(Duration::from_secs(5)), "5s");
+    }
+}
@@ -0,0 +1,195 @@
+use crate::utils::{command, paths};
+use anyhow::{Context, Result};
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Path pattern whose matches created during a run are removed afterwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leftover {
+    /// Matching paths are removed unless they existed before the run
+    Created(&'static str),
+    /// Like `Created`, and files that existed before are truncated back to their original length
+    Appended(&'static str),
+}
+
+impl Leftover {
+    fn pattern(&self) -> &'static str {
+        match self {
+            Leftover::Created(pattern) | Leftover::Appended(pattern) => pattern,
+        }
+    }
+}
+
+/// Paths matching leftover patterns, captured before a run
+#[derive(Debug)]
+pub struct Leftovers {
+    patterns: Vec<Leftover>,
+    existing: HashMap<PathBuf, u64>,
+}
+
+impl Leftovers {
+    /// Record which paths match the patterns before anything is installed
+    pub fn capture(patterns: &[Leftover]) -> Self {
+        let existing = patterns
+            .iter()
+            .flat_map(|leftover| paths::expand(leftover.pattern()))
+            .map(|path| {
+                let len = fs::symlink_metadata(&path).map(|m| m.len()).unwrap_or(0);
+                (path, len)
+            })
+            .collect();
+
+        Self {
+            patterns: patterns.to_vec(),
+            existing,
+        }
+    }
+
+    /// Paths that match the patterns now but did not exist when captured
+    pub fn created(&self) -> Vec<PathBuf> {
+        self.patterns
+            .iter()
+            .flat_map(|leftover| paths::expand(leftover.pattern()))
+            .filter(|path| !self.existing.contains_key(path))
+            .collect()
+    }
+
+    /// Remove paths created since the capture and restore appended files
+    pub fn remove(&self) -> Result<()> {
+        for path in self.created() {
+            // Entries inside a created directory are gone once the directory is removed
+            if fs::symlink_metadata(&path).is_err() {
+                continue;
+            }
+            debug!("Removing leftover: {}", path.display());
+            remove_path(&path)?;
+        }
+
+        for leftover in &self.patterns {
+            let Leftover::Appended(pattern) = leftover else {
+                continue;
+            };
+            for path in paths::expand(pattern) {
+                if let Some(&original_len) = self.existing.get(&path) {
+                    truncate_file(&path, original_len)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Leftovers live under root-owned directories, so changes that are denied go through sudo
+fn remove_path(path: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read metadata: {}", path.display()))?;
+    let removed = if metadata.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match removed {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            let mut cmd = Command::new("sudo");
+            cmd.arg("rm").arg("-rf").arg("--").arg(path);
+            command::run(cmd).map(drop).map_err(anyhow::Error::from)
+        }
+        removed => removed.map_err(anyhow::Error::from),
+    }
+    .with_context(|| format!("Failed to remove leftover: {}", path.display()))
+}
+
+fn truncate_file(path: &Path, len: u64) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read metadata: {}", path.display()))?;
+    if !metadata.is_file() || metadata.len() <= len {
+        return Ok(());
+    }
+
+    debug!(
+        "Truncating {} from {} to {} bytes",
+        path.display(),
+        metadata.len(),
+        len
+    );
+    match fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_len(len))
+    {
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            let mut cmd = Command::new("sudo");
+            cmd.arg("truncate")
+                .arg("-s")
+                .arg(len.to_string())
+                .arg("--")
+                .arg(path);
+            command::run(cmd).map(drop).map_err(anyhow::Error::from)
+        }
+        truncated => truncated.map_err(anyhow::Error::from),
+    }
+    .with_context(|| format!("Failed to truncate: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leak(s: String) -> &'static str {
+        Box::leak(s.into_boxed_str())
+    }
+
+    #[test]
+    fn test_removes_only_created_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let lists = temp_dir.path().join("lists");
+        fs::create_dir_all(lists.join("partial")).unwrap();
+        fs::write(lists.join("existing_Packages"), "old").unwrap();
+
+        let pattern = leak(format!("{}/*", lists.display()));
+        let leftovers = Leftovers::capture(&[Leftover::Created(pattern)]);
+
+        fs::write(lists.join("existing_Packages"), "refreshed").unwrap();
+        fs::write(lists.join("new_Packages"), "new").unwrap();
+        fs::create_dir_all(lists.join("auxfiles")).unwrap();
+        assert_eq!(leftovers.created().len(), 2);
+
+        leftovers.remove().unwrap();
+        assert!(lists.join("existing_Packages").exists());
+        assert!(lists.join("partial").exists());
+        assert!(!lists.join("new_Packages").exists());
+        assert!(!lists.join("auxfiles").exists());
+    }
+
+    #[test]
+    fn test_truncates_appended_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = temp_dir.path().join("dpkg.log");
+        fs::write(&log, "before\n").unwrap();
+
+        let pattern = leak(log.display().to_string());
+        let leftovers = Leftovers::capture(&[Leftover::Appended(pattern)]);
+
+        fs::write(&log, "before\nduring install\n").unwrap();
+        leftovers.remove().unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap(), "before\n");
+    }
+
+    #[test]
+    fn test_removes_created_appended_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = temp_dir.path().join("history.log");
+
+        let pattern = leak(format!("{}/*", temp_dir.path().display()));
+        let leftovers = Leftovers::capture(&[Leftover::Appended(pattern)]);
+
+        fs::write(&log, "created during install\n").unwrap();
+        leftovers.remove().unwrap();
+        assert!(!log.exists());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::installers::package_manager::InstalledPackage;
+use anyhow::{Context, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Exact package versions resolved by an install
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub packages: BTreeMap<String, String>,
+}
+
+impl Lockfile {
+    /// Build a lockfile from the installed versions of the requested packages
+    pub fn from_installed(packages: &[String], installed: &[InstalledPackage]) -> Self {
+        let mut locked = BTreeMap::new();
+        for package in packages {
+            let name = package_name(package);
+            match installed.iter().find(|p| p.name == name) {
+                Some(p) => {
+                    locked.insert(p.name.clone(), p.version.clone());
+                }
+                None => warn!(
+                    "Could not find installed version of {}, not locking it",
+                    name
+                ),
+            }
+        }
+        Self { packages: locked }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read lockfile: {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse lockfile: {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(path, contents + "\n")
+            .with_context(|| format!("Failed to write lockfile: {}", path.display()))
+    }
+
+    /// Pin each package to its locked version (e.g. `curl=7.88.1-10`)
+    pub fn pin(&self, packages: &[String]) -> Result<Vec<String>> {
+        packages
+            .iter()
+            .map(|package| {
+                let name = package_name(package);
+                let version = self
+                    .packages
+                    .get(name)
+                    .with_context(|| format!("Package {} is not in the lockfile", name))?;
+                Ok(format!("{}={}", name, version))
+            })
+            .collect()
+    }
+}
+
+/// Strip a version constraint from a package spec (e.g. `curl=7.88.1-10` or `curl>8`)
+pub fn package_name(spec: &str) -> &str {
+    spec.split(['=', '<', '>', '~'])
+        .next()
+        .unwrap_or(spec)
+        .trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(name: &str, version: &str) -> InstalledPackage {
+        InstalledPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_package_name() {
+        assert_eq!(package_name("curl"), "curl");
+        assert_eq!(package_name("curl=7.88.1-10"), "curl");
+        assert_eq!(package_name("curl>8"), "curl");
+        assert_eq!(package_name("curl~8.5"), "curl");
+    }
+
+    #[test]
+    fn test_lockfile_round_trip() {
+        let packages = vec!["curl".to_string(), "git=1:2.39.2-1".to_string()];
+        let lockfile = Lockfile::from_installed(
+            &packages,
+            &[
+                installed("curl", "7.88.1-10"),
+                installed("git", "1:2.39.2-1"),
+                installed("libc6", "2.36-9"),
+            ],
+        );
+        assert_eq!(lockfile.packages.len(), 2);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("picolayer.lock");
+        lockfile.save(&path).unwrap();
+        let loaded = Lockfile::load(&path).unwrap();
+        assert_eq!(loaded, lockfile);
+
+        assert_eq!(
+            loaded.pin(&packages).unwrap(),
+            vec!["curl=7.88.1-10".to_string(), "git=1:2.39.2-1".to_string()]
+        );
+        assert!(loaded.pin(&["wget".to_string()]).is_err());
+    }
+}
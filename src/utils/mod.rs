@@ -1,4 +1,14 @@
 pub mod analytics;
+pub mod command;
+pub mod download;
+pub mod github;
+pub mod leftovers;
+pub mod lockfile;
 pub mod logging;
 pub mod os_detect;
+pub mod output;
+pub mod paths;
 pub mod pkgx;
+pub mod receipts;
+pub mod snapshot;
+pub mod sweep;
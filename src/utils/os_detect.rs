@@ -6,53 +6,61 @@ pub enum LinuxDistro {
     Ubuntu,
     Debian,
     Alpine,
+    Fedora,
+    Rhel,
+    CentOs,
+    Rocky,
+    Alma,
+    OpenSuseLeap,
+    OpenSuseTumbleweed,
+    Sles,
+    Arch,
     Other,
 }
 
-/// Detect the Linux distribution
-pub fn detect_distro() -> Result<LinuxDistro> {
-    if let Ok(contents) = fs::read_to_string("/etc/os-release") {
-        let mut kv = std::collections::HashMap::new();
+impl LinuxDistro {
+    /// Distributions with a supported native package manager
+    pub const SUPPORTED: &[LinuxDistro] = &[
+        LinuxDistro::Ubuntu,
+        LinuxDistro::Debian,
+        LinuxDistro::Alpine,
+        LinuxDistro::Fedora,
+        LinuxDistro::Rhel,
+        LinuxDistro::CentOs,
+        LinuxDistro::Rocky,
+        LinuxDistro::Alma,
+        LinuxDistro::OpenSuseLeap,
+        LinuxDistro::OpenSuseTumbleweed,
+        LinuxDistro::Sles,
+        LinuxDistro::Arch,
+    ];
 
-        for line in contents.lines() {
-            let line = line.trim();
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            if let Some(pos) = line.find('=') {
-                let key = line[..pos].trim().to_ascii_uppercase();
-                let mut val = line[pos + 1..]
-                    .trim()
-                    .trim_matches(|c| c == '"' || c == '\'')
-                    .to_string();
-                if key == "ID_LIKE" {
-                    val = val.replace(&[',', ';'][..], " ");
-                }
-                kv.insert(key, val);
-            }
+    /// Names identifying the distribution, most specific first
+    pub fn names(&self) -> &'static [&'static str] {
+        match self {
+            LinuxDistro::Ubuntu => &["ubuntu", "debian"],
+            LinuxDistro::Debian => &["debian"],
+            LinuxDistro::Alpine => &["alpine"],
+            LinuxDistro::Fedora => &["fedora"],
+            LinuxDistro::Rhel => &["rhel", "fedora"],
+            LinuxDistro::CentOs => &["centos", "rhel", "fedora"],
+            LinuxDistro::Rocky => &["rocky", "rhel", "fedora"],
+            LinuxDistro::Alma => &["almalinux", "rhel", "fedora"],
+            LinuxDistro::OpenSuseLeap => &["opensuse-leap", "opensuse", "suse"],
+            LinuxDistro::OpenSuseTumbleweed => &["opensuse-tumbleweed", "opensuse", "suse"],
+            LinuxDistro::Sles => &["sles", "suse"],
+            LinuxDistro::Arch => &["arch"],
+            LinuxDistro::Other => &[],
         }
+    }
+}
 
-        let id = kv.get("ID").map(|s| s.as_str()).unwrap_or_default();
-        let id_like = kv.get("ID_LIKE").map(|s| s.as_str()).unwrap_or_default();
-
-        let matches_any = |target: &str| {
-            if id.eq_ignore_ascii_case(target) {
-                return true;
-            }
-            id_like
-                .split_whitespace()
-                .any(|token| token.eq_ignore_ascii_case(target))
-        };
-
-        if matches_any("ubuntu") {
-            return Ok(LinuxDistro::Ubuntu);
-        }
-        if matches_any("alpine") {
-            return Ok(LinuxDistro::Alpine);
-        }
-        if matches_any("debian") {
-            return Ok(LinuxDistro::Debian);
-        }
+/// Detect the Linux distribution
+pub fn detect_distro() -> Result<LinuxDistro> {
+    if let Ok(contents) = fs::read_to_string("/etc/os-release")
+        && let Some(distro) = distro_from_os_release(&contents)
+    {
+        return Ok(distro);
     }
 
     if fs::metadata("/etc/alpine-release").is_ok() {
@@ -61,6 +69,18 @@ pub fn detect_distro() -> Result<LinuxDistro> {
     if fs::metadata("/etc/debian_version").is_ok() {
         return Ok(LinuxDistro::Debian);
     }
+    if fs::metadata("/etc/fedora-release").is_ok() {
+        return Ok(LinuxDistro::Fedora);
+    }
+    if fs::metadata("/etc/redhat-release").is_ok() {
+        return Ok(LinuxDistro::Rhel);
+    }
+    if fs::metadata("/etc/SuSE-release").is_ok() {
+        return Ok(LinuxDistro::Sles);
+    }
+    if fs::metadata("/etc/arch-release").is_ok() {
+        return Ok(LinuxDistro::Arch);
+    }
     if let Ok(contents) = fs::read_to_string("/etc/lsb-release") {
         for line in contents.lines() {
             let line = line.trim();
@@ -79,6 +99,82 @@ pub fn detect_distro() -> Result<LinuxDistro> {
     Ok(LinuxDistro::Other)
 }
 
+/// Identify the distribution from the `ID` and `ID_LIKE` fields of os-release contents
+fn distro_from_os_release(contents: &str) -> Option<LinuxDistro> {
+    let mut kv = std::collections::HashMap::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(pos) = line.find('=') {
+            let key = line[..pos].trim().to_ascii_uppercase();
+            let mut val = line[pos + 1..]
+                .trim()
+                .trim_matches(|c| c == '"' || c == '\'')
+                .to_string();
+            if key == "ID_LIKE" {
+                val = val.replace(&[',', ';'][..], " ");
+            }
+            kv.insert(key, val);
+        }
+    }
+
+    let id = kv.get("ID").map(|s| s.as_str()).unwrap_or_default();
+    let id_like = kv.get("ID_LIKE").map(|s| s.as_str()).unwrap_or_default();
+
+    let matches_any = |target: &str| {
+        if id.eq_ignore_ascii_case(target) {
+            return true;
+        }
+        id_like
+            .split_whitespace()
+            .any(|token| token.eq_ignore_ascii_case(target))
+    };
+
+    if matches_any("ubuntu") {
+        return Some(LinuxDistro::Ubuntu);
+    }
+    if matches_any("alpine") {
+        return Some(LinuxDistro::Alpine);
+    }
+    if matches_any("debian") {
+        return Some(LinuxDistro::Debian);
+    }
+    // Derivatives list their parents in ID_LIKE, so check the most specific first
+    if matches_any("rocky") {
+        return Some(LinuxDistro::Rocky);
+    }
+    if matches_any("almalinux") {
+        return Some(LinuxDistro::Alma);
+    }
+    if matches_any("centos") {
+        return Some(LinuxDistro::CentOs);
+    }
+    if matches_any("rhel") {
+        return Some(LinuxDistro::Rhel);
+    }
+    if matches_any("fedora") {
+        return Some(LinuxDistro::Fedora);
+    }
+    if matches_any("opensuse-tumbleweed") {
+        return Some(LinuxDistro::OpenSuseTumbleweed);
+    }
+    if matches_any("opensuse-leap") || matches_any("opensuse") {
+        return Some(LinuxDistro::OpenSuseLeap);
+    }
+    if matches_any("sles") || matches_any("sled") || matches_any("sle-micro") || matches_any("suse")
+    {
+        return Some(LinuxDistro::Sles);
+    }
+    if matches_any("arch") {
+        return Some(LinuxDistro::Arch);
+    }
+
+    None
+}
+
 /// Check if the system is Ubuntu
 pub fn is_ubuntu() -> bool {
     matches!(detect_distro(), Ok(LinuxDistro::Ubuntu))
@@ -97,6 +193,31 @@ pub fn is_alpine() -> bool {
     matches!(detect_distro(), Ok(LinuxDistro::Alpine))
 }
 
+/// Check if the system is Fedora or RHEL-like (RHEL, CentOS, Rocky, AlmaLinux)
+pub fn is_fedora_like() -> bool {
+    matches!(
+        detect_distro(),
+        Ok(LinuxDistro::Fedora)
+            | Ok(LinuxDistro::Rhel)
+            | Ok(LinuxDistro::CentOs)
+            | Ok(LinuxDistro::Rocky)
+            | Ok(LinuxDistro::Alma)
+    )
+}
+
+/// Check if the system is openSUSE or SUSE Linux Enterprise
+pub fn is_suse_like() -> bool {
+    matches!(
+        detect_distro(),
+        Ok(LinuxDistro::OpenSuseLeap) | Ok(LinuxDistro::OpenSuseTumbleweed) | Ok(LinuxDistro::Sles)
+    )
+}
+
+/// Check if the system is Arch Linux or an Arch derivative (e.g. Manjaro)
+pub fn is_arch_like() -> bool {
+    matches!(detect_distro(), Ok(LinuxDistro::Arch))
+}
+
 #[cfg(test)]
 mod distro_tests {
     use super::*;
@@ -106,4 +227,51 @@ mod distro_tests {
         let result = detect_distro();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_distro_from_os_release() {
+        let cases = [
+            ("ID=ubuntu\nID_LIKE=debian\n", LinuxDistro::Ubuntu),
+            ("ID=debian\n", LinuxDistro::Debian),
+            ("ID=alpine\n", LinuxDistro::Alpine),
+            ("ID=fedora\n", LinuxDistro::Fedora),
+            ("ID=\"rhel\"\nID_LIKE=\"fedora\"\n", LinuxDistro::Rhel),
+            (
+                "ID=\"centos\"\nID_LIKE=\"rhel fedora\"\n",
+                LinuxDistro::CentOs,
+            ),
+            (
+                "ID=\"rocky\"\nID_LIKE=\"rhel centos fedora\"\n",
+                LinuxDistro::Rocky,
+            ),
+            (
+                "ID=\"almalinux\"\nID_LIKE=\"rhel centos fedora\"\n",
+                LinuxDistro::Alma,
+            ),
+            ("ID=\"ol\"\nID_LIKE=\"fedora\"\n", LinuxDistro::Fedora),
+            (
+                "ID=\"opensuse-leap\"\nID_LIKE=\"suse opensuse\"\n",
+                LinuxDistro::OpenSuseLeap,
+            ),
+            (
+                "ID=\"opensuse-tumbleweed\"\nID_LIKE=\"opensuse suse\"\n",
+                LinuxDistro::OpenSuseTumbleweed,
+            ),
+            ("ID=\"sles\"\nID_LIKE=\"suse\"\n", LinuxDistro::Sles),
+            ("ID=\"sle-micro\"\nID_LIKE=\"suse\"\n", LinuxDistro::Sles),
+            ("ID=arch\n", LinuxDistro::Arch),
+            ("ID=manjaro\nID_LIKE=arch\n", LinuxDistro::Arch),
+        ];
+
+        for (contents, expected) in cases {
+            assert_eq!(distro_from_os_release(contents), Some(expected));
+        }
+        assert_eq!(distro_from_os_release("ID=gentoo\n"), None);
+    }
+
+    #[test]
+    fn test_distro_names() {
+        assert_eq!(LinuxDistro::Ubuntu.names(), &["ubuntu", "debian"]);
+        assert!(LinuxDistro::Other.names().is_empty());
+    }
 }
@@ -0,0 +1,69 @@
+use crate::utils::snapshot::LayerDiff;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::process::Stdio;
+
+/// Format of the result printed when a command finishes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Plans on dry runs and layer reports when requested, logs otherwise
+    #[default]
+    Text,
+    /// A single JSON document describing the result
+    Json,
+}
+
+impl OutputFormat {
+    /// Standard output for commands run on the user's behalf, sent to stderr with
+    /// JSON output so that stdout carries only the JSON document
+    pub fn child_stdout(self) -> Stdio {
+        match self {
+            OutputFormat::Text => Stdio::inherit(),
+            OutputFormat::Json => std::io::stderr().into(),
+        }
+    }
+}
+
+/// Result of a command printed with `--output json`
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub command: &'a str,
+    pub dry_run: bool,
+    pub elapsed_secs: f64,
+    /// Net bytes added to the watched directories, measured with `--layer-report` or
+    /// `--max-layer-size`
+    pub bytes_added: Option<i64>,
+    /// Per-directory breakdown, included with `--layer-report`
+    pub layer: Option<&'a LayerDiff>,
+    /// The installed packages, release, feature or tool, or the plan on dry runs
+    pub result: serde_json::Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_report_json() {
+        let diff = LayerDiff {
+            roots: vec![(PathBuf::from("/usr"), 4096)],
+            largest_new_files: vec![(PathBuf::from("/usr/local/bin/gh"), 4096)],
+            bytes_written: 4096,
+            bytes_removed: 0,
+        };
+        let report = Report {
+            command: "gh-release",
+            dry_run: false,
+            elapsed_secs: 1.5,
+            bytes_added: Some(diff.total()),
+            layer: None,
+            result: serde_json::json!({ "tag": "v2.50.0" }),
+        };
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["command"], "gh-release");
+        assert_eq!(json["bytes_added"], 4096);
+        assert!(json["layer"].is_null());
+        assert_eq!(json["result"]["tag"], "v2.50.0");
+    }
+}
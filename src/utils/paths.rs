@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Expand a path pattern into the existing paths it matches
+///
+/// A leading `~` is replaced by the home directory and `*` wildcards are
+/// supported in the final path component only (e.g. `/var/cache/debconf/*-old`).
+/// A `/**/` component matches any depth (e.g. `/usr/**/__pycache__`).
+pub fn expand(pattern: &str) -> Vec<PathBuf> {
+    let pattern = expand_home(pattern);
+    if let Some((base, file_pattern)) = pattern.split_once("/**/") {
+        return expand_recursive(Path::new(base), file_pattern);
+    }
+    let path = Path::new(&pattern);
+
+    let Some(file_pattern) = path.file_name().and_then(|f| f.to_str()) else {
+        return existing(path);
+    };
+    if !file_pattern.contains('*') {
+        return existing(path);
+    }
+
+    let parent = path.parent().unwrap_or(Path::new("/"));
+    let Ok(entries) = fs::read_dir(parent) else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| wildcard_match(file_pattern, name))
+        })
+        .map(|entry| entry.path())
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Replace a leading `~` with the value of `$HOME`
+pub fn expand_home(pattern: &str) -> String {
+    match (pattern.strip_prefix('~'), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.trim_end_matches('/'), rest)
+        }
+        _ => pattern.to_string(),
+    }
+}
+
+/// Find entries at any depth below `base` whose file name matches the pattern
+fn expand_recursive(base: &Path, file_pattern: &str) -> Vec<PathBuf> {
+    let mut matches: Vec<PathBuf> = walkdir::WalkDir::new(base)
+        .min_depth(1)
+        .same_file_system(true)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| wildcard_match(file_pattern, name))
+        })
+        .map(|entry| entry.into_path())
+        .collect();
+    matches.sort();
+    matches
+}
+
+fn existing(path: &Path) -> Vec<PathBuf> {
+    if fs::symlink_metadata(path).is_ok() {
+        vec![path.to_path_buf()]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Match a file name against a pattern where `*` matches any sequence of characters
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+    if name.len() < first.len() + last.len() || !name.starts_with(first) || !name.ends_with(last) {
+        return false;
+    }
+
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("*-old", "config.dat-old"));
+        assert!(wildcard_match("__db.*", "__db.001"));
+        assert!(wildcard_match("a*b*c", "a-x-b-y-c"));
+        assert!(!wildcard_match("*-old", "config.dat"));
+        assert!(!wildcard_match("ab*ba", "aba"));
+        assert!(wildcard_match("exact", "exact"));
+        assert!(!wildcard_match("exact", "exactly"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for name in ["config.dat", "config.dat-old", "templates.dat-old"] {
+            fs::write(temp_dir.path().join(name), "").unwrap();
+        }
+        let dir = temp_dir.path().display();
+
+        let old = expand(&format!("{}/*-old", dir));
+        assert_eq!(
+            old,
+            vec![
+                temp_dir.path().join("config.dat-old"),
+                temp_dir.path().join("templates.dat-old")
+            ]
+        );
+        assert_eq!(expand(&format!("{}/*", dir)).len(), 3);
+        assert_eq!(expand(&format!("{}/config.dat", dir)).len(), 1);
+        assert!(expand(&format!("{}/missing", dir)).is_empty());
+        assert!(expand(&format!("{}/missing/*", dir)).is_empty());
+    }
+
+    #[test]
+    fn test_expand_recursive() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let nested = temp_dir.path().join("lib/python3/site-packages/pkg");
+        fs::create_dir_all(nested.join("__pycache__")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("lib/python3/__pycache__")).unwrap();
+        fs::write(nested.join("module.py"), "").unwrap();
+        let dir = temp_dir.path().display();
+
+        assert_eq!(
+            expand(&format!("{}/**/__pycache__", dir)),
+            vec![
+                temp_dir.path().join("lib/python3/__pycache__"),
+                nested.join("__pycache__")
+            ]
+        );
+        assert_eq!(
+            expand(&format!("{}/**/*.py", dir)),
+            vec![nested.join("module.py")]
+        );
+        assert!(expand(&format!("{}/missing/**/*", dir)).is_empty());
+    }
+}
@@ -0,0 +1,556 @@
+use crate::utils::command;
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory holding the receipts database unless `PICOLAYER_STATE_DIR` is set
+const DEFAULT_STATE_DIR: &str = "/var/lib/picolayer";
+const RECEIPTS_DB: &str = "receipts.db";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS receipts (
+    name TEXT PRIMARY KEY,
+    source TEXT NOT NULL,
+    reference TEXT NOT NULL,
+    version TEXT NOT NULL,
+    checksum TEXT,
+    options TEXT NOT NULL,
+    installed_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS files (
+    receipt TEXT NOT NULL REFERENCES receipts(name) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    sha256 TEXT
+);
+CREATE TABLE IF NOT EXISTS packages (
+    receipt TEXT NOT NULL REFERENCES receipts(name) ON DELETE CASCADE,
+    manager TEXT NOT NULL,
+    name TEXT NOT NULL
+);
+";
+
+/// Record of an install and the files it wrote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Receipt {
+    pub name: String,
+    /// Installer that wrote the files (e.g. `gh-release`)
+    pub source: String,
+    /// Repository or OCI reference the install came from
+    pub reference: String,
+    pub version: String,
+    /// Verified checksum of the downloaded artifact
+    pub checksum: Option<String>,
+    /// Settings the install ran with
+    pub options: BTreeMap<String, String>,
+    /// Seconds since the Unix epoch
+    pub installed_at: u64,
+    pub files: Vec<ReceiptFile>,
+    /// System packages a devcontainer feature added, removed through their package manager.
+    /// Packages installed directly with a package manager subcommand get no receipt
+    pub packages: Vec<ReceiptPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReceiptFile {
+    pub path: PathBuf,
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReceiptPackage {
+    /// Package manager that installed the package (e.g. `apt-get`)
+    pub manager: String,
+    pub name: String,
+}
+
+/// A removed install, along with the recorded files left in place
+#[derive(Debug, Serialize)]
+pub struct Uninstalled {
+    #[serde(flatten)]
+    pub receipt: Receipt,
+    /// Files that changed since the install or that another install also lists
+    pub kept: Vec<PathBuf>,
+}
+
+impl Uninstalled {
+    /// Number of recorded files that were removed or already gone
+    pub fn removed(&self) -> usize {
+        self.receipt.files.len() - self.kept.len()
+    }
+}
+
+/// Receipts listed by `picolayer list`
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct Receipts(pub Vec<Receipt>);
+
+impl Receipt {
+    pub fn new(name: &str, source: &str, reference: &str, version: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            source: source.to_string(),
+            reference: reference.to_string(),
+            version: version.to_string(),
+            checksum: None,
+            options: BTreeMap::new(),
+            installed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            files: Vec::new(),
+            packages: Vec::new(),
+        }
+    }
+
+    /// Whether every recorded file still exists with the contents it was written with
+    pub fn files_unchanged(&self) -> bool {
+        self.files.iter().all(|file| match &file.sha256 {
+            Some(sha256) => hash_file(&file.path).is_ok_and(|hash| &hash == sha256),
+            None => file.path.exists(),
+        })
+    }
+}
+
+impl ReceiptFile {
+    /// A written file along with the hash of its contents
+    pub fn hashed(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            sha256: Some(hash_file(path)?),
+        })
+    }
+}
+
+impl fmt::Display for Receipt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} {} ({} from {})",
+            self.name, self.version, self.source, self.reference
+        )?;
+        if let Some(checksum) = &self.checksum {
+            writeln!(f, "  checksum: {}", checksum)?;
+        }
+        for (key, value) in &self.options {
+            writeln!(f, "  option: {}={}", key, value)?;
+        }
+        for file in &self.files {
+            match &file.sha256 {
+                Some(sha256) => writeln!(f, "  file: {} (sha256:{})", file.path.display(), sha256)?,
+                None => writeln!(f, "  file: {}", file.path.display())?,
+            }
+        }
+        for package in &self.packages {
+            writeln!(f, "  package: {} ({})", package.name, package.manager)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Receipts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "No installs recorded");
+        }
+        for receipt in &self.0 {
+            writeln!(
+                f,
+                "{:<40} {:<20} {:<22} {} files",
+                receipt.name,
+                receipt.version,
+                receipt.source,
+                receipt.files.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Directory holding the receipts database
+pub fn state_dir() -> PathBuf {
+    std::env::var_os("PICOLAYER_STATE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_STATE_DIR))
+}
+
+/// Receipts database under a state directory
+pub struct ReceiptStore {
+    conn: Connection,
+}
+
+impl ReceiptStore {
+    /// Open the store for recording, creating it if needed
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        let conn = Connection::open(dir.join(RECEIPTS_DB))
+            .with_context(|| format!("Failed to open receipts in {}", dir.display()))?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create receipts tables")?;
+        Ok(Self { conn })
+    }
+
+    /// Open the store without writing to it, or `None` if nothing was recorded yet
+    pub fn open_read_only(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(RECEIPTS_DB);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let conn = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Failed to open receipts in {}", dir.display()))?;
+        Ok(Some(Self { conn }))
+    }
+
+    /// Record a receipt, replacing an earlier one with the same name
+    pub fn record(&mut self, receipt: &Receipt) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "DELETE FROM files WHERE receipt = ?1",
+            params![receipt.name],
+        )?;
+        tx.execute(
+            "DELETE FROM packages WHERE receipt = ?1",
+            params![receipt.name],
+        )?;
+        tx.execute(
+            "INSERT OR REPLACE INTO receipts (name, source, reference, version, checksum, options, installed_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                receipt.name,
+                receipt.source,
+                receipt.reference,
+                receipt.version,
+                receipt.checksum,
+                serde_json::to_string(&receipt.options)?,
+                receipt.installed_at as i64,
+            ],
+        )?;
+        for file in &receipt.files {
+            tx.execute(
+                "INSERT INTO files (receipt, path, sha256) VALUES (?1, ?2, ?3)",
+                params![receipt.name, file.path.to_string_lossy(), file.sha256],
+            )?;
+        }
+        for package in &receipt.packages {
+            tx.execute(
+                "INSERT INTO packages (receipt, manager, name) VALUES (?1, ?2, ?3)",
+                params![receipt.name, package.manager, package.name],
+            )?;
+        }
+        tx.commit().context("Failed to record receipt")
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<Receipt>> {
+        let receipt = self
+            .conn
+            .query_row(
+                "SELECT name, source, reference, version, checksum, options, installed_at
+                 FROM receipts WHERE name = ?1",
+                params![name],
+                |row| {
+                    Ok((
+                        Receipt {
+                            name: row.get(0)?,
+                            source: row.get(1)?,
+                            reference: row.get(2)?,
+                            version: row.get(3)?,
+                            checksum: row.get(4)?,
+                            options: BTreeMap::new(),
+                            installed_at: row.get::<_, i64>(6)? as u64,
+                            files: Vec::new(),
+                            packages: Vec::new(),
+                        },
+                        row.get::<_, String>(5)?,
+                    ))
+                },
+            )
+            .optional()?;
+
+        let Some((mut receipt, options)) = receipt else {
+            return Ok(None);
+        };
+        receipt.options = serde_json::from_str(&options)
+            .with_context(|| format!("Invalid options in receipt for {}", name))?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT path, sha256 FROM files WHERE receipt = ?1 ORDER BY rowid")?;
+        receipt.files = stmt
+            .query_map(params![name], |row| {
+                Ok(ReceiptFile {
+                    path: PathBuf::from(row.get::<_, String>(0)?),
+                    sha256: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT manager, name FROM packages WHERE receipt = ?1 ORDER BY rowid")?;
+        receipt.packages = stmt
+            .query_map(params![name], |row| {
+                Ok(ReceiptPackage {
+                    manager: row.get(0)?,
+                    name: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(Some(receipt))
+    }
+
+    pub fn list(&self) -> Result<Vec<Receipt>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM receipts ORDER BY name")?;
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        names
+            .iter()
+            .filter_map(|name| self.get(name).transpose())
+            .collect()
+    }
+
+    /// Remove the files a receipt lists, then the receipt itself. Files whose contents
+    /// changed since the install, or that another receipt also lists, are kept
+    pub fn uninstall(&mut self, name: &str) -> Result<Uninstalled> {
+        let receipt = self.find(name)?;
+        let mut kept = Vec::new();
+        for file in &receipt.files {
+            if let Some(other) = self.other_owner(name, &file.path)? {
+                warn!(
+                    "Keeping {}, which {} also installed",
+                    file.path.display(),
+                    other
+                );
+                kept.push(file.path.clone());
+                continue;
+            }
+            if let Some(sha256) = &file.sha256
+                && file.path.exists()
+                && hash_file(&file.path).ok().as_ref() != Some(sha256)
+            {
+                warn!(
+                    "Keeping {}, which changed since it was installed",
+                    file.path.display()
+                );
+                kept.push(file.path.clone());
+                continue;
+            }
+
+            remove_file(&file.path)?;
+        }
+        self.conn
+            .execute("DELETE FROM files WHERE receipt = ?1", params![name])?;
+        self.conn
+            .execute("DELETE FROM packages WHERE receipt = ?1", params![name])?;
+        self.conn
+            .execute("DELETE FROM receipts WHERE name = ?1", params![name])?;
+        let uninstalled = Uninstalled { receipt, kept };
+        info!("Uninstalled {} ({} files)", name, uninstalled.removed());
+        Ok(uninstalled)
+    }
+
+    /// Another receipt listing the same file, if any
+    fn other_owner(&self, name: &str, path: &Path) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT receipt FROM files WHERE path = ?1 AND receipt != ?2 LIMIT 1",
+                params![path.to_string_lossy(), name],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn find(&self, name: &str) -> Result<Receipt> {
+        self.get(name)?
+            .with_context(|| format!("No install recorded for {}", name))
+    }
+}
+
+/// Record a receipt in the state directory
+pub fn record(receipt: &Receipt) -> Result<()> {
+    ReceiptStore::open(&state_dir())?.record(receipt)?;
+    debug!("Recorded receipt for {}", receipt.name);
+    Ok(())
+}
+
+/// All recorded receipts
+pub fn list() -> Result<Receipts> {
+    match ReceiptStore::open_read_only(&state_dir())? {
+        Some(store) => Ok(Receipts(store.list()?)),
+        None => Ok(Receipts(Vec::new())),
+    }
+}
+
+/// The receipt recorded for an install, if any
+pub fn get(name: &str) -> Result<Option<Receipt>> {
+    match ReceiptStore::open_read_only(&state_dir())? {
+        Some(store) => store.get(name),
+        None => Ok(None),
+    }
+}
+
+/// The receipt recorded for an install
+pub fn find(name: &str) -> Result<Receipt> {
+    ReceiptStore::open_read_only(&state_dir())?
+        .with_context(|| format!("No install recorded for {}", name))?
+        .find(name)
+}
+
+/// Remove the files an install wrote and forget its receipt
+pub fn uninstall(name: &str) -> Result<Uninstalled> {
+    let dir = state_dir();
+    anyhow::ensure!(
+        dir.join(RECEIPTS_DB).exists(),
+        "No install recorded for {}",
+        name
+    );
+    ReceiptStore::open(&dir)?.uninstall(name)
+}
+
+/// Remove an installed file, retrying with sudo when it lies in a directory such as
+/// `/usr/local/bin` that the current user cannot write
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => debug!("Removed {}", path.display()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            debug!("Already removed: {}", path.display())
+        }
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            let mut cmd = Command::new("sudo");
+            cmd.arg("rm").arg("-f").arg("--").arg(path);
+            command::run(cmd).with_context(|| format!("Failed to remove {}", path.display()))?;
+            debug!("Removed {}", path.display());
+        }
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to remove {}", path.display()));
+        }
+    }
+    Ok(())
+}
+
+/// SHA-256 of a file's contents as lowercase hex
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(dir: &Path) -> Receipt {
+        let binary = dir.join("gh");
+        fs::write(&binary, "binary").unwrap();
+
+        let mut receipt = Receipt::new("cli/cli", "gh-release", "cli/cli", "v2.50.0");
+        receipt.checksum = Some("sha256:abc".to_string());
+        receipt
+            .options
+            .insert("install_dir".to_string(), dir.display().to_string());
+        receipt.files.push(ReceiptFile::hashed(&binary).unwrap());
+        receipt.packages.push(ReceiptPackage {
+            manager: "apt-get".to_string(),
+            name: "libsecret-1-0".to_string(),
+        });
+        receipt
+    }
+
+    #[test]
+    fn test_record_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join("state");
+        assert!(ReceiptStore::open_read_only(&state).unwrap().is_none());
+
+        let receipt = receipt(dir.path());
+        let mut store = ReceiptStore::open(&state).unwrap();
+        store.record(&receipt).unwrap();
+        store.record(&receipt).unwrap();
+
+        let store = ReceiptStore::open_read_only(&state).unwrap().unwrap();
+        assert_eq!(store.get("cli/cli").unwrap(), Some(receipt.clone()));
+        assert_eq!(store.list().unwrap(), vec![receipt.clone()]);
+        assert!(store.get("node").unwrap().is_none());
+
+        assert!(receipt.files_unchanged());
+        fs::write(dir.path().join("gh"), "replaced").unwrap();
+        assert!(!receipt.files_unchanged());
+    }
+
+    #[test]
+    fn test_uninstall() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut receipt = receipt(dir.path());
+        receipt.files.push(ReceiptFile {
+            path: dir.path().join("already-removed"),
+            sha256: None,
+        });
+        let kept = dir.path().join("kept");
+        fs::write(&kept, "kept").unwrap();
+
+        let mut store = ReceiptStore::open(&dir.path().join("state")).unwrap();
+        store.record(&receipt).unwrap();
+        let uninstalled = store.uninstall("cli/cli").unwrap();
+        assert_eq!(uninstalled.receipt, receipt);
+        assert!(uninstalled.kept.is_empty());
+
+        assert!(!dir.path().join("gh").exists());
+        assert!(kept.exists());
+        assert!(store.get("cli/cli").unwrap().is_none());
+        assert!(store.uninstall("cli/cli").is_err());
+    }
+
+    #[test]
+    fn test_uninstall_keeps_changed_and_shared_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut receipt = receipt(dir.path());
+        let shared = dir.path().join("shared");
+        fs::write(&shared, "shared").unwrap();
+        receipt.files.push(ReceiptFile::hashed(&shared).unwrap());
+
+        let mut other = Receipt::new("other", "url", "https://example.com", "1.0.0");
+        other.files.push(ReceiptFile::hashed(&shared).unwrap());
+
+        let mut store = ReceiptStore::open(&dir.path().join("state")).unwrap();
+        store.record(&receipt).unwrap();
+        store.record(&other).unwrap();
+        fs::write(dir.path().join("gh"), "edited").unwrap();
+
+        let uninstalled = store.uninstall("cli/cli").unwrap();
+        assert_eq!(
+            uninstalled.kept,
+            vec![dir.path().join("gh"), shared.clone()]
+        );
+        assert_eq!(uninstalled.removed(), 0);
+        assert!(dir.path().join("gh").exists());
+        assert!(shared.exists());
+        assert!(store.get("cli/cli").unwrap().is_none());
+        assert!(store.get("other").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_hash_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, "hello world").unwrap();
+        assert_eq!(
+            hash_file(&path).unwrap(),
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Directories watched for layer size changes when none are given
+pub const DEFAULT_ROOTS: &[&str] = &["/usr", "/var", "/etc", "/opt", "/tmp", "~"];
+
+/// Number of new files listed in the layer report
+const LARGEST_FILES: usize = 10;
+
+/// File sizes under a set of watched directories at a point in time
+#[derive(Debug)]
+pub struct Snapshot {
+    roots: Vec<PathBuf>,
+    files: HashMap<PathBuf, FileState>,
+}
+
+/// Size and modification time, to tell rewritten files of the same size apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Size change of a layer between two snapshots
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct LayerDiff {
+    /// Bytes added (or removed, if negative) under each watched directory
+    pub roots: Vec<(PathBuf, i64)>,
+    /// Largest files that did not exist before, biggest first
+    pub largest_new_files: Vec<(PathBuf, u64)>,
+    /// Full size of the files that are new or changed, which a layer stores in full
+    pub bytes_written: u64,
+    /// Size of the files that were removed, which do not shrink the layer
+    pub bytes_removed: u64,
+}
+
+impl Snapshot {
+    /// Walk the roots and record the size of every file, staying on each root's filesystem
+    pub fn capture(roots: &[PathBuf]) -> Self {
+        let roots = distinct_roots(roots);
+        let mut files = HashMap::new();
+
+        for root in &roots {
+            for entry in walkdir::WalkDir::new(root)
+                .same_file_system(true)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| !entry.file_type().is_dir())
+            {
+                let metadata = entry.metadata().ok();
+                let state = FileState {
+                    len: metadata.as_ref().map_or(0, |m| m.len()),
+                    modified: metadata.and_then(|m| m.modified().ok()),
+                };
+                files.insert(entry.into_path(), state);
+            }
+        }
+
+        Self { roots, files }
+    }
+
+    /// Compare this snapshot with a later one of the same roots
+    pub fn diff(&self, after: &Snapshot) -> LayerDiff {
+        let mut roots: Vec<(PathBuf, i64)> =
+            self.roots.iter().map(|root| (root.clone(), 0)).collect();
+        let mut largest_new_files = Vec::new();
+        let mut bytes_written = 0;
+        let mut bytes_removed = 0;
+
+        let added = after.files.iter().map(|(path, state)| {
+            let before = self.files.get(path);
+            match before {
+                None => {
+                    largest_new_files.push((path.clone(), state.len));
+                    bytes_written += state.len;
+                }
+                Some(before) if before != state => bytes_written += state.len,
+                Some(_) => {}
+            }
+            (path, state.len as i64 - before.map_or(0, |b| b.len) as i64)
+        });
+        let removed = self
+            .files
+            .iter()
+            .filter(|(path, _)| !after.files.contains_key(*path))
+            .map(|(path, state)| {
+                bytes_removed += state.len;
+                (path, -(state.len as i64))
+            });
+
+        for (path, delta) in added.chain(removed).collect::<Vec<_>>() {
+            if let Some((_, bytes)) = roots.iter_mut().find(|(root, _)| path.starts_with(root)) {
+                *bytes += delta;
+            }
+        }
+
+        largest_new_files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        largest_new_files.truncate(LARGEST_FILES);
+
+        LayerDiff {
+            roots,
+            largest_new_files,
+            bytes_written,
+            bytes_removed,
+        }
+    }
+
+    /// Files in a later snapshot that did not exist in this one, sorted by path
+    pub fn new_files(&self, after: &Snapshot) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = after
+            .files
+            .keys()
+            .filter(|path| !self.files.contains_key(*path))
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+impl LayerDiff {
+    /// Net bytes added across all watched directories
+    pub fn total(&self) -> i64 {
+        self.roots.iter().map(|(_, bytes)| bytes).sum()
+    }
+
+    /// Fail if new and changed files take more than `max_bytes`; removals do not count,
+    /// since a layer records them without reclaiming space from the layers below
+    pub fn check_budget(&self, max_bytes: u64) -> Result<()> {
+        if self.bytes_written > max_bytes {
+            anyhow::bail!(
+                "Layer size {} exceeds the budget of {}",
+                format_size(self.bytes_written as i64),
+                format_size(max_bytes as i64)
+            );
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LayerDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Layer size: {} ({} written, {} removed)",
+            format_size(self.total()),
+            format_size(self.bytes_written as i64),
+            format_size(self.bytes_removed as i64)
+        )?;
+        for (root, bytes) in &self.roots {
+            writeln!(f, "  {:>10}  {}", format_size(*bytes), root.display())?;
+        }
+        if !self.largest_new_files.is_empty() {
+            writeln!(f, "Largest new files:")?;
+            for (path, bytes) in &self.largest_new_files {
+                writeln!(
+                    f,
+                    "  {:>10}  {}",
+                    format_size(*bytes as i64),
+                    path.display()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolve the watched directories, expanding `~` and dropping missing or nested ones
+pub fn watched_roots(roots: &[String]) -> Vec<PathBuf> {
+    let patterns: Vec<&str> = if roots.is_empty() {
+        DEFAULT_ROOTS.to_vec()
+    } else {
+        roots.iter().map(String::as_str).collect()
+    };
+
+    patterns
+        .into_iter()
+        .map(|root| PathBuf::from(crate::utils::paths::expand_home(root)))
+        .filter(|root| root.is_dir())
+        .collect()
+}
+
+/// Drop duplicate roots and roots nested in another root so no file is counted twice
+fn distinct_roots(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut distinct: Vec<PathBuf> = Vec::new();
+    for root in roots {
+        if roots
+            .iter()
+            .any(|other| other != root && root.starts_with(other))
+            || distinct.contains(root)
+        {
+            continue;
+        }
+        distinct.push(root.clone());
+    }
+    distinct
+}
+
+/// Parse a size such as `512K`, `50M` or `1G` (binary units) into bytes
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let unit_start = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(unit_start);
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("Invalid size: {}", size))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => anyhow::bail!("Invalid size unit in '{}', expected K, M or G", size),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .with_context(|| format!("Size is too large: {}", size))
+}
+
+/// Format a byte count using binary units
+pub fn format_size(bytes: i64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes.unsigned_abs() as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    let sign = if bytes < 0 { "-" } else { "" };
+    if unit == 0 {
+        format!("{}{} {}", sign, bytes.unsigned_abs(), UNITS[unit])
+    } else {
+        format!("{}{:.1} {}", sign, value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("512K").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("50M").unwrap(), 50 * 1024 * 1024);
+        assert_eq!(parse_size("1GiB").unwrap(), 1024 * 1024 * 1024);
+        assert_eq!(parse_size("2mb").unwrap(), 2 * 1024 * 1024);
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10X").is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(-50 * 1024 * 1024), "-50.0 MiB");
+    }
+
+    #[test]
+    fn test_distinct_roots() {
+        let roots = vec![
+            PathBuf::from("/usr"),
+            PathBuf::from("/usr/local"),
+            PathBuf::from("/var"),
+            PathBuf::from("/var"),
+        ];
+        assert_eq!(
+            distinct_roots(&roots),
+            vec![PathBuf::from("/usr"), PathBuf::from("/var")]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let usr = temp_dir.path().join("usr");
+        let var = temp_dir.path().join("var");
+        fs::create_dir_all(usr.join("bin")).unwrap();
+        fs::create_dir_all(&var).unwrap();
+        fs::write(usr.join("bin/existing"), vec![0u8; 100]).unwrap();
+        fs::write(var.join("stale"), vec![0u8; 30]).unwrap();
+
+        let roots = vec![usr.clone(), var.clone()];
+        let before = Snapshot::capture(&roots);
+
+        fs::write(usr.join("bin/existing"), vec![0u8; 150]).unwrap();
+        fs::write(usr.join("bin/tool"), vec![0u8; 1000]).unwrap();
+        fs::write(var.join("small"), vec![0u8; 10]).unwrap();
+        fs::remove_file(var.join("stale")).unwrap();
+
+        let after = Snapshot::capture(&roots);
+        assert_eq!(
+            before.new_files(&after),
+            vec![usr.join("bin/tool"), var.join("small")]
+        );
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.roots, vec![(usr.clone(), 1050), (var.clone(), -20)]);
+        assert_eq!(diff.total(), 1030);
+        assert_eq!(
+            diff.largest_new_files,
+            vec![(usr.join("bin/tool"), 1000), (var.join("small"), 10)]
+        );
+        assert_eq!(diff.bytes_written, 1160);
+        assert_eq!(diff.bytes_removed, 30);
+        assert!(diff.check_budget(2048).is_ok());
+        // The budget counts changed files in full and ignores the removal
+        assert!(diff.check_budget(1100).is_err());
+
+        let report = diff.to_string();
+        assert!(report.contains("Layer size: 1.0 KiB (1.1 KiB written, 30 B removed)"));
+        assert!(report.contains("Largest new files:"));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::utils::leftovers::{Leftover, Leftovers};
+use clap::ValueEnum;
+
+/// Named groups of paths swept after a command when they were created during the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SweepProfile {
+    /// Temporary files and user caches
+    Minimal,
+    /// Documentation, man and info pages
+    Docs,
+    /// Translations and locale data
+    Locales,
+    /// Python bytecode caches
+    Pycache,
+}
+
+impl SweepProfile {
+    /// Paths removed by this profile
+    pub fn leftovers(&self) -> &'static [Leftover] {
+        match self {
+            SweepProfile::Minimal => &[
+                Leftover::Created("/tmp/*"),
+                Leftover::Created("/var/tmp/*"),
+                Leftover::Created("~/.cache"),
+                Leftover::Created("~/.cache/*"),
+            ],
+            SweepProfile::Docs => &[
+                Leftover::Created("/usr/share/doc/**/*"),
+                Leftover::Created("/usr/share/doc-base/*"),
+                Leftover::Created("/usr/share/man/**/*"),
+                Leftover::Created("/usr/share/info/*"),
+                Leftover::Created("/usr/local/share/doc/**/*"),
+                Leftover::Created("/usr/local/share/man/**/*"),
+            ],
+            SweepProfile::Locales => &[
+                Leftover::Created("/usr/share/locale/**/*"),
+                Leftover::Created("/usr/local/share/locale/**/*"),
+            ],
+            SweepProfile::Pycache => &[
+                Leftover::Created("/usr/**/__pycache__"),
+                Leftover::Created("/opt/**/__pycache__"),
+                Leftover::Created("~/**/__pycache__"),
+            ],
+        }
+    }
+}
+
+/// Record the paths covered by the profiles before a command runs
+pub fn capture(profiles: &[SweepProfile]) -> Leftovers {
+    let mut patterns: Vec<Leftover> = Vec::new();
+    for profile in profiles {
+        for leftover in profile.leftovers() {
+            if !patterns.contains(leftover) {
+                patterns.push(*leftover);
+            }
+        }
+    }
+    Leftovers::capture(&patterns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_names() {
+        assert_eq!(
+            SweepProfile::from_str("pycache", true).unwrap(),
+            SweepProfile::Pycache
+        );
+        assert!(SweepProfile::from_str("everything", true).is_err());
+    }
+}
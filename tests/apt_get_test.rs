@@ -32,3 +32,56 @@ fn test_apt_get_installation() {
         String::from_utf8_lossy(&output.stderr)
     );
 }
+
+#[test]
+#[serial]
+#[cfg(target_os = "linux")]
+fn test_apt_get_installation_with_repo() {
+    // The Docker repository below only serves Debian suites
+    let (Some("debian"), Some(codename)) = (
+        common::os_release("ID").as_deref(),
+        common::os_release("VERSION_CODENAME"),
+    ) else {
+        println!("Skipping: not running on Debian");
+        return;
+    };
+
+    let repo = format!(
+        "https://download.docker.com/linux/debian/gpg https://download.docker.com/linux/debian {} stable",
+        codename
+    );
+    let output = run_picolayer(&["apt-get", "docker-ce-cli", "--repo", &repo]);
+
+    assert!(
+        output.status.success(),
+        "apt-get installation failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Expect the keyring and source to be removed after installing
+    assert!(
+        !std::path::Path::new(
+            "/etc/apt/sources.list.d/picolayer-download-docker-com-linux-debian.sources"
+        )
+        .exists()
+    );
+    assert!(
+        !std::path::Path::new("/etc/apt/keyrings/picolayer-download-docker-com-linux-debian.gpg")
+            .exists()
+    );
+}
+
+#[test]
+#[serial]
+#[cfg(target_os = "linux")]
+fn test_apt_get_skips_installed_packages() {
+    let output = run_picolayer(&["apt-get", "bash"]);
+
+    // Expect no update or install when the package is already present
+    assert!(
+        output.status.success(),
+        "apt-get installation failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("already installed: bash"));
+}
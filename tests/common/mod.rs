@@ -31,6 +31,16 @@ pub fn is_transient_error(stderr: &str) -> bool {
         || stderr.contains("connection")
 }
 
+/// Value of a field in /etc/os-release, e.g. `VERSION_CODENAME`
+#[allow(dead_code)]
+pub fn os_release(key: &str) -> Option<String> {
+    std::fs::read_to_string("/etc/os-release")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+        .map(|value| value.trim_matches('"').to_string())
+}
+
 /// Check if a binary exists at the given path
 #[allow(dead_code)]
 pub fn binary_exists(path: &str) -> bool {
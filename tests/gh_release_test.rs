@@ -597,3 +597,52 @@ fn test_xz_extraction_handles_empty_data() {
         stderr
     );
 }
+
+#[test]
+#[serial]
+fn test_missing_token_file_fails_before_fetching() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let token_file = temp_dir.path().join("missing-token");
+
+    let output = run_picolayer(&[
+        "gh-release",
+        "pkgxdev/pkgx",
+        "pkgx",
+        "--install-dir",
+        temp_dir.path().to_str().unwrap(),
+        "--token-file",
+        token_file.to_str().unwrap(),
+    ]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to read token file"),
+        "Should report the unreadable token file: {}",
+        stderr
+    );
+}
+
+#[test]
+#[serial]
+fn test_gitea_source_requires_api_url() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+
+    let output = run_picolayer(&[
+        "gh-release",
+        "forgejo/forgejo",
+        "forgejo",
+        "--install-dir",
+        temp_dir.path().to_str().unwrap(),
+        "--source",
+        "forgejo",
+    ]);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--api-url is required for gitea releases"),
+        "Should ask for the API URL: {}",
+        stderr
+    );
+}
@@ -25,6 +25,9 @@ fn test_apt_get_help() {
     assert!(output.status.success());
     let stdout = String::from_utf8_lossy(&output.stdout);
     assert!(stdout.contains("apt-get"));
+    assert!(stdout.contains("--lock"));
+    assert!(stdout.contains("--locked"));
+    assert!(stdout.contains("--repo"));
 }
 
 #[test]
@@ -33,6 +36,32 @@ fn test_apk_help() {
     assert!(output.status.success());
     let stdout = String::from_utf8_lossy(&output.stdout);
     assert!(stdout.contains("apk"));
+    assert!(stdout.contains("--repository"));
+    assert!(stdout.contains("--key"));
+}
+
+#[test]
+fn test_dnf_help() {
+    let output = run_picolayer(&["dnf", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("dnf"));
+}
+
+#[test]
+fn test_zypper_help() {
+    let output = run_picolayer(&["zypper", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("zypper"));
+}
+
+#[test]
+fn test_pacman_help() {
+    let output = run_picolayer(&["pacman", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("pacman"));
 }
 
 #[test]
@@ -43,6 +72,22 @@ fn test_brew_help() {
     assert!(stdout.contains("brew"));
 }
 
+#[test]
+fn test_pkg_help() {
+    let output = run_picolayer(&["pkg", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--map"));
+}
+
+#[test]
+fn test_pkg_invalid_mapping() {
+    let output = run_picolayer(&["pkg", "curl", "--map", "alpine:build-base"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("at least two distributions"));
+}
+
 #[test]
 fn test_gh_release_help() {
     let output = run_picolayer(&["gh-release", "--help"]);
@@ -58,3 +103,214 @@ fn test_run_help() {
     let stdout = String::from_utf8_lossy(&output.stdout);
     assert!(stdout.contains("x"));
 }
+
+#[test]
+fn test_layer_flags_help() {
+    let output = run_picolayer(&["apk", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--layer-report"));
+    assert!(stdout.contains("--max-layer-size"));
+    assert!(stdout.contains("--sweep"));
+}
+
+#[test]
+fn test_invalid_max_layer_size() {
+    let output = run_picolayer(&["--max-layer-size", "lots", "apk", "curl"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid size"));
+}
+
+#[test]
+fn test_invalid_sweep_profile() {
+    let output = run_picolayer(&["--sweep", "minimal,everything", "apk", "curl"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("everything"));
+}
+
+#[test]
+fn test_locked_requires_lock() {
+    let output = run_picolayer(&["apt-get", "curl", "--locked"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--lock"));
+}
+
+#[test]
+fn test_apt_get_invalid_repo() {
+    let output = run_picolayer(&["apt-get", "curl", "--repo", "https://example.com/key.gpg"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("KEYRING URI SUITE"));
+}
+
+#[test]
+fn test_build_help() {
+    let output = run_picolayer(&["build", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--build-deps"));
+}
+
+#[test]
+fn test_build_requires_command() {
+    let output = run_picolayer(&["build", "--build-deps", "make"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_apply_help() {
+    let output = run_picolayer(&["apply", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("manifest"));
+}
+
+#[test]
+fn test_apply_missing_manifest() {
+    let output = run_picolayer(&["apply", "/nonexistent/picolayer.toml"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Failed to read manifest"));
+}
+
+#[test]
+fn test_dry_run_help() {
+    let output = run_picolayer(&["gh-release", "--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("--dry-run"));
+}
+
+#[test]
+fn test_x_dry_run() {
+    let output = run_picolayer(&[
+        "--dry-run",
+        "x",
+        "--env",
+        "FOO=bar",
+        "python@3.12",
+        "--version",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("x would run python (3.12)"));
+    assert!(stdout.contains("command: --version"));
+    assert!(stdout.contains("env: FOO=bar"));
+}
+
+#[test]
+fn test_x_dry_run_json() {
+    let output = run_picolayer(&[
+        "--dry-run",
+        "--output",
+        "json",
+        "x",
+        "python@3.12",
+        "--version",
+    ]);
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["command"], "x");
+    assert_eq!(report["dry_run"], true);
+    assert!(report["bytes_added"].is_null());
+    assert_eq!(report["result"]["tool"], "python");
+    assert_eq!(report["result"]["version"], "3.12");
+}
+
+#[test]
+fn test_invalid_output_format() {
+    let output = run_picolayer(&["--output", "yaml", "apk", "curl"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("yaml"));
+}
+
+#[test]
+fn test_list_without_receipts() {
+    let state_dir = tempfile::tempdir().unwrap();
+    let output = std::process::Command::new(common::PICOLAYER_BIN)
+        .env("PICOLAYER_STATE_DIR", state_dir.path())
+        .arg("list")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No installs recorded"));
+}
+
+#[test]
+fn test_uninstall_unknown_install() {
+    let state_dir = tempfile::tempdir().unwrap();
+    for command in ["info", "uninstall"] {
+        let output = std::process::Command::new(common::PICOLAYER_BIN)
+            .env("PICOLAYER_STATE_DIR", state_dir.path())
+            .args([command, "cli/cli"])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("No install recorded for cli/cli"));
+    }
+}
+
+#[test]
+fn test_force_help() {
+    let output = run_picolayer(&["gh-release", "--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("--force"));
+}
+
+#[test]
+fn test_upgrade_check_without_receipts() {
+    let state_dir = tempfile::tempdir().unwrap();
+    let output = std::process::Command::new(common::PICOLAYER_BIN)
+        .env("PICOLAYER_STATE_DIR", state_dir.path())
+        .args(["upgrade", "--check"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("up to date"));
+
+    let output = std::process::Command::new(common::PICOLAYER_BIN)
+        .env("PICOLAYER_STATE_DIR", state_dir.path())
+        .args(["upgrade", "cli/cli"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No install recorded for cli/cli"));
+}
+
+#[test]
+fn test_url_dry_run() {
+    let output = run_picolayer(&[
+        "--dry-run",
+        "url",
+        "https://releases.example.com/tool/{version}/tool_{version}_{os}_{arch}.zip",
+        "tool",
+        "--version",
+        "1.2.3",
+        "--install-dir",
+        "/opt/bin",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("url would install https://releases.example.com/tool/1.2.3/tool_1.2.3_")
+    );
+    assert!(!stdout.contains("{os}") && !stdout.contains("{arch}"));
+    assert!(stdout.contains("binary: /opt/bin/tool"));
+}
+
+#[test]
+fn test_url_requires_version_for_placeholder() {
+    let output = run_picolayer(&[
+        "--dry-run",
+        "url",
+        "https://releases.example.com/tool/{version}/tool.zip",
+        "tool",
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no --version was given"));
+}